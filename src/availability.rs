@@ -0,0 +1,166 @@
+/**
+ * src/availability.rs
+ * Copyright (c) 2021 Carlos Carral <carloscarral13@gmail.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+use crate::grid::{Day, Grid, TimeRange};
+use chrono::NaiveTime;
+use serde::{Deserialize, Serialize};
+use strum::IntoEnumIterator;
+
+/// When classes can take place, regardless of which pools they belong to.
+///
+/// i.e: "I work Tuesdays 14:00 - 18:00" is a forbidden window, "nothing before
+/// 09:00" is a forbidden window on every day, and "Fridays off" is an empty
+/// list of allowed windows on Friday.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Availability {
+    /// Per day windows in which no class can take place.
+    #[serde(default)]
+    pub forbidden: [Vec<TimeRange>; 7],
+    /// Per day windows classes must fall within. `None` leaves the day
+    /// unrestricted, while an empty list rules out the whole day.
+    #[serde(default)]
+    pub allowed: [Option<Vec<TimeRange>>; 7],
+}
+
+/// A time range of a grid that breaks an `Availability`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Violation {
+    pub day: Day,
+    /// Time range of the grid.
+    pub time_range: TimeRange,
+    /// Forbidden window the time range overlaps, or `None` when it doesn't fit
+    /// within any allowed window.
+    pub window: Option<TimeRange>,
+}
+
+impl Availability {
+    /// Availability with no restrictions.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn forbid(&mut self, day: Day, window: TimeRange) {
+        self.forbidden[day as usize].push(window);
+    }
+
+    /// Adds `window` to the ones classes on `day` must fall within.
+    pub fn allow(&mut self, day: Day, window: TimeRange) {
+        self.allowed[day as usize]
+            .get_or_insert_with(Vec::new)
+            .push(window);
+    }
+
+    pub fn day_off(&mut self, day: Day) {
+        self.allowed[day as usize] = Some(vec![]);
+    }
+
+    /// Forbids classes starting before `time` on every day.
+    pub fn not_before(&mut self, time: NaiveTime) {
+        for day in Day::iter() {
+            self.forbid(day, (NaiveTime::MIN, time));
+        }
+    }
+
+    /// Forbids classes ending after `time` on every day.
+    pub fn not_after(&mut self, time: NaiveTime) {
+        let end_of_day = NaiveTime::from_hms_opt(23, 59, 59).unwrap();
+        for day in Day::iter() {
+            self.forbid(day, (time, end_of_day));
+        }
+    }
+
+    /// Returns every time range of `grid` that breaks this availability.
+    pub fn violations<Id: Eq, D>(&self, grid: &Grid<Id, D>) -> Vec<Violation> {
+        let mut violations = vec![];
+
+        for day in Day::iter() {
+            for &(start, end) in grid.time_values[day as usize].iter() {
+                for &window in self.forbidden[day as usize].iter() {
+                    if start < window.1 && window.0 < end {
+                        violations.push(Violation {
+                            day,
+                            time_range: (start, end),
+                            window: Some(window),
+                        });
+                    }
+                }
+
+                if let Some(allowed) = &self.allowed[day as usize] {
+                    if !allowed.iter().any(|w| w.0 <= start && end <= w.1) {
+                        violations.push(Violation {
+                            day,
+                            time_range: (start, end),
+                            window: None,
+                        });
+                    }
+                }
+            }
+        }
+
+        violations
+    }
+
+    pub fn permits<Id: Eq, D>(&self, grid: &Grid<Id, D>) -> bool {
+        self.violations(grid).is_empty()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::utils::time;
+
+    fn grid(day: Day, start: &str, end: &str) -> Grid<u32, ()> {
+        let mut time_values: [Vec<TimeRange>; 7] = Default::default();
+        time_values[day as usize] = vec![(time(start), time(end))];
+        Grid::new(1, time_values, ()).unwrap()
+    }
+
+    #[test]
+    fn test_availability() {
+        let mut availability = Availability::new();
+        assert!(availability.permits(&grid(Day::MONDAY, "07:00", "08:00")));
+
+        availability.forbid(Day::TUESDAY, (time("14:00"), time("18:00")));
+        availability.not_before(time("09:00"));
+        availability.day_off(Day::FRIDAY);
+        availability.allow(Day::SATURDAY, (time("10:00"), time("13:00")));
+
+        assert!(availability.permits(&grid(Day::TUESDAY, "12:00", "14:00")));
+        assert!(availability.permits(&grid(Day::SATURDAY, "10:00", "13:00")));
+        assert!(availability.permits(&grid(Day::MONDAY, "09:00", "10:00")));
+
+        assert_eq!(
+            availability.violations(&grid(Day::TUESDAY, "17:00", "19:00")),
+            vec![Violation {
+                day: Day::TUESDAY,
+                time_range: (time("17:00"), time("19:00")),
+                window: Some((time("14:00"), time("18:00"))),
+            }]
+        );
+        assert_eq!(
+            availability.violations(&grid(Day::SATURDAY, "12:00", "14:00")),
+            vec![Violation {
+                day: Day::SATURDAY,
+                time_range: (time("12:00"), time("14:00")),
+                window: None,
+            }]
+        );
+        assert!(!availability.permits(&grid(Day::FRIDAY, "10:00", "11:00")));
+        assert!(!availability.permits(&grid(Day::WEDNESDAY, "08:00", "10:00")));
+    }
+}
@@ -0,0 +1,160 @@
+/**
+ * src/compat.rs
+ * Copyright (c) 2021 Carlos Carral <carloscarral13@gmail.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+use crate::grid::{GapRule, Pool};
+use std::ops::Range;
+
+/// Set of grids, identified by their position in a `CompatibilityMatrix`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct GridSet {
+    words: Vec<u64>,
+}
+
+impl GridSet {
+    pub fn new(len: usize) -> Self {
+        GridSet {
+            words: vec![0; len.div_ceil(64)],
+        }
+    }
+
+    pub fn insert(&mut self, index: usize) {
+        self.words[index / 64] |= 1 << (index % 64);
+    }
+
+    pub fn contains(&self, index: usize) -> bool {
+        self.words[index / 64] & (1 << (index % 64)) != 0
+    }
+
+    /// Number of grids of the set within `range`.
+    pub fn count_in(&self, range: Range<usize>) -> usize {
+        range.filter(|&index| self.contains(index)).count()
+    }
+
+    /// Overwrites `self` with the intersection of `a` and `b`.
+    pub fn assign_intersection(&mut self, a: &GridSet, b: &GridSet) {
+        for ((word, a), b) in self.words.iter_mut().zip(&a.words).zip(&b.words) {
+            *word = a & b;
+        }
+    }
+}
+
+/// Tells, for every pair of grids across all pools, whether both can be part
+/// of the same schedule.
+///
+/// Grids are identified by `(pool, grid)`, their positions in the pool list and
+/// in the pool's grid list. Grids of the same pool are never compatible, since
+/// a schedule only holds one grid of each pool.
+#[derive(Clone, Debug)]
+pub struct CompatibilityMatrix {
+    /// `offsets[pool]` is the position of the first grid of `pool` once every
+    /// grid list is laid out one after the other.
+    offsets: Vec<usize>,
+    /// `rows[i]` holds every grid that is compatible with grid `i`.
+    rows: Vec<GridSet>,
+}
+
+impl CompatibilityMatrix {
+    pub fn new<Id: Eq, D>(pool_list: &[Pool<Id, D>]) -> Self {
+        Self::with_gap(pool_list, &GapRule::default())
+    }
+
+    /// Same as `new`, but grids that are closer than `gap` allows are not
+    /// compatible either.
+    pub fn with_gap<Id: Eq, D>(pool_list: &[Pool<Id, D>], gap: &GapRule<Id, D>) -> Self {
+        let mut offsets = Vec::with_capacity(pool_list.len() + 1);
+        let mut len = 0;
+        for pool in pool_list {
+            offsets.push(len);
+            len += pool.grid_list.len();
+        }
+        offsets.push(len);
+
+        let grids: Vec<_> = pool_list
+            .iter()
+            .enumerate()
+            .flat_map(|(pool, p)| p.grid_list.iter().map(move |grid| (pool, grid)))
+            .collect();
+
+        let mut rows = vec![GridSet::new(len); len];
+        for (i, (pool_i, grid_i)) in grids.iter().enumerate() {
+            for (j, (pool_j, grid_j)) in grids.iter().enumerate().skip(i + 1) {
+                if pool_i != pool_j && gap.allows(grid_i, grid_j) {
+                    rows[i].insert(j);
+                    rows[j].insert(i);
+                }
+            }
+        }
+
+        CompatibilityMatrix { offsets, rows }
+    }
+
+    /// Total number of grids across all pools.
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    /// Whether grid `a.1` of pool `a.0` can be taken along with grid `b.1` of pool `b.0`.
+    pub fn compatible(&self, a: (usize, usize), b: (usize, usize)) -> bool {
+        self.rows[self.index(a)].contains(self.index(b))
+    }
+
+    /// Every grid that can be taken along with grid `grid.1` of pool `grid.0`.
+    pub fn compatible_with(&self, grid: (usize, usize)) -> Vec<(usize, usize)> {
+        let row = &self.rows[self.index(grid)];
+        self.offsets
+            .windows(2)
+            .enumerate()
+            .flat_map(|(pool, w)| (w[0]..w[1]).map(move |j| (pool, j - w[0], j)))
+            .filter(|&(_, _, j)| row.contains(j))
+            .map(|(pool, other, _)| (pool, other))
+            .collect()
+    }
+
+    /// Same table, indexed as `table[pool_a][grid_a][pool_b][grid_b]`.
+    pub fn to_nested(&self) -> Vec<Vec<Vec<Vec<bool>>>> {
+        let pools = || self.offsets.windows(2).map(|w| w[0]..w[1]);
+
+        pools()
+            .map(|grids| {
+                grids
+                    .map(|i| {
+                        pools()
+                            .map(|others| others.map(|j| self.rows[i].contains(j)).collect())
+                            .collect()
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    pub(crate) fn index(&self, (pool, grid): (usize, usize)) -> usize {
+        self.offsets[pool] + grid
+    }
+
+    /// Positions of the grids of `pool`.
+    pub(crate) fn grids_of(&self, pool: usize) -> Range<usize> {
+        self.offsets[pool]..self.offsets[pool + 1]
+    }
+
+    pub(crate) fn row(&self, index: usize) -> &GridSet {
+        &self.rows[index]
+    }
+}
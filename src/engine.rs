@@ -15,33 +15,476 @@
  * You should have received a copy of the GNU General Public License
  * along with this program.  If not, see <http://www.gnu.org/licenses/>.
  */
-use crate::grid::{Grid, Pool, Schedule};
-use anyhow::{bail, Result};
-use core::fmt::Debug;
+use crate::availability::Availability;
+use crate::compat::{CompatibilityMatrix, GridSet};
+use crate::error::ScheduleError;
+use crate::grid::{Day, GapRule, Grid, Pool, Schedule, TimeRange};
+use crate::observer::EngineObserver;
+use crate::score::{rank, Criterion, Scorer, Weighted};
+use crate::travel::TravelTimes;
 use itertools::Itertools;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use std::ops::RangeInclusive;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// Number of visited nodes between two progress reports.
+const PROGRESS_INTERVAL: u64 = 4096;
+
+/// Inclusive range for the number of grids that conform a schedule.
+/// Deserializes either from a single number, for an exact bound, or from
+/// `{ "min": .., "max": .. }`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "BoundRepr")]
+pub struct Bound {
+    pub min: usize,
+    pub max: usize,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum BoundRepr {
+    Exact(usize),
+    Range { min: usize, max: usize },
+}
+
+impl From<BoundRepr> for Bound {
+    fn from(repr: BoundRepr) -> Self {
+        match repr {
+            BoundRepr::Exact(bound) => bound.into(),
+            BoundRepr::Range { min, max } => Bound { min, max },
+        }
+    }
+}
+
+impl From<usize> for Bound {
+    fn from(bound: usize) -> Self {
+        Bound {
+            min: bound,
+            max: bound,
+        }
+    }
+}
+
+impl From<RangeInclusive<usize>> for Bound {
+    fn from(range: RangeInclusive<usize>) -> Self {
+        Bound {
+            min: *range.start(),
+            max: *range.end(),
+        }
+    }
+}
 
 #[derive(Deserialize)]
-pub struct EngineParams<Id: Eq + Clone, D> {
+pub struct EngineParams<Id: Eq + Clone, D: Clone> {
     /// List of grids that serve as a starting point for the algorithm.
     /// It will try to merge these grids into a valid schedule and then
     /// perform the combinatorial analysis.
     pub seeds: Vec<Grid<Id, D>>,
-    /// Number of grids that will conform a schedule.
-    pub bound: usize,
+    /// Number of grids, seeds included, that will conform a schedule. When it
+    /// is a range, schedules of every size within it are generated.
+    pub bound: Bound,
 
     /// List of pools over which the algorithm will perform the
     /// combinatorial analysis.
     pub pool_list: Vec<Pool<Id, D>>,
+
+    /// When present, results are sorted from best to worst according to
+    /// this weighted combination of criteria.
+    #[serde(default)]
+    pub ranking: Option<Weighted<Criterion>>,
+
+    /// Maximum number of schedules to return. Combined with `ranking`,
+    /// only the best `top_k` schedules are searched for.
+    #[serde(default)]
+    pub top_k: Option<usize>,
+
+    /// The search stops once this many schedules have been found.
+    #[serde(default)]
+    pub max_results: Option<usize>,
+
+    /// The search stops once this many partial schedules have been visited.
+    #[serde(default)]
+    pub max_nodes: Option<u64>,
+
+    /// The search stops as soon as this token is cancelled.
+    #[serde(skip)]
+    pub cancel: Option<CancelToken>,
+
+    /// When classes can take place. Seeds must respect it, and grids that
+    /// don't are left out of the search.
+    #[serde(default)]
+    pub availability: Availability,
+
+    /// Break required between classes on the same day.
+    #[serde(default)]
+    pub gap: GapRule<Id, D>,
+
+    /// Minutes it takes to get between the locations of grids. Classes on the
+    /// same day need at least that long a break between them.
+    #[serde(default)]
+    pub travel_times: TravelTimes,
+
+    /// What to do when seeds clash with one another.
+    #[serde(default)]
+    pub seed_policy: SeedPolicy,
+
+    /// Gets told about seed merging, combinations, pruned branches and leaves.
+    #[serde(skip)]
+    pub observer: Option<Box<dyn EngineObserver<Id, D>>>,
+}
+
+impl<Id: Eq + Clone, D: Clone> EngineParams<Id, D> {
+    /// Params with no seeds, limits, ranking or restrictions, which can be
+    /// set afterwards.
+    pub fn new(pool_list: Vec<Pool<Id, D>>, bound: impl Into<Bound>) -> Self {
+        EngineParams {
+            seeds: vec![],
+            bound: bound.into(),
+            pool_list,
+            ranking: None,
+            top_k: None,
+            max_results: None,
+            max_nodes: None,
+            cancel: None,
+            availability: Availability::default(),
+            gap: GapRule::default(),
+            travel_times: TravelTimes::default(),
+            seed_policy: SeedPolicy::default(),
+            observer: None,
+        }
+    }
+}
+
+/// What the engine does when some seeds can't be taken together.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SeedPolicy {
+    /// Fail with `ScheduleError::SeedConflict`.
+    #[default]
+    Strict,
+    /// Drop as few seeds as possible so that the rest can be taken together.
+    /// Among equally large subsets, earlier seeds are preferred. Seeds outside
+    /// `EngineParams::availability` are always dropped.
+    LargestCompatible,
+}
+
+/// Describes a clash between two seeds.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SeedClash<Id> {
+    /// Positions of both seeds in `EngineParams::seeds`, the first one lower.
+    pub seeds: (usize, usize),
+    /// Pools both seeds belong to.
+    pub pools: (Id, Id),
+    pub day: Day,
+    /// Interval in which both seeds overlap, or the break between them when
+    /// it is shorter than `required_minutes`.
+    pub interval: TimeRange,
+    /// Break required between both seeds by the `GapRule`.
+    pub required_minutes: u32,
+}
+
+/// Shared flag that stops a running search from elsewhere, e.g. from another
+/// thread or from a progress callback.
+#[derive(Clone, Debug, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// How far along a search is.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct Progress {
+    /// Combinations of pools that have been fully traversed.
+    pub combinations_done: u64,
+    /// Combinations of pools in the whole search.
+    pub combinations_total: u64,
+    /// Partial schedules, complete ones included, visited so far.
+    pub nodes_visited: u64,
+}
+
+/// Schedules found by a search, along with whether the search ran to completion.
+#[derive(Debug, Serialize)]
+pub struct SearchOutcome<Id: Eq + Clone, D: Clone> {
+    pub schedules: Vec<Schedule<Id, D>>,
+    /// False when the search stopped early because of `max_results`,
+    /// `max_nodes`, `top_k` without ranking, or a cancellation.
+    pub exhaustive: bool,
+    pub progress: Progress,
+    /// Positions of the seeds left out because of `SeedPolicy::LargestCompatible`.
+    pub dropped_seeds: Vec<usize>,
+    /// Grids left out because of `EngineParams::availability`, as (pool, grid)
+    /// indices into `EngineParams::pool_list`.
+    pub unavailable_grids: Vec<(usize, usize)>,
 }
 
 /// Given a list of schedule pools, the engine generates all possible combinations
 /// of pools that satisfy an upper bound and finds all valid paths from these pools.
-pub fn engine_main<Id: Eq + Clone + Debug, D: Clone + Debug>(
+pub fn engine_main<Id: Eq + Clone, D: Clone>(
+    params: EngineParams<Id, D>,
+) -> Result<Vec<Schedule<Id, D>>, ScheduleError<Id>> {
+    Ok(search(params)?.schedules)
+}
+
+/// Same as `engine_main`, but also tells whether the search was exhaustive.
+pub fn search<Id: Eq + Clone, D: Clone>(
     params: EngineParams<Id, D>,
-) -> Result<Vec<Schedule<Id, D>>> {
-    if params.bound > params.pool_list.len() + params.seeds.len() {
-        bail!("Bound can't be larger than length of pool list + length of seeds.");
+) -> Result<SearchOutcome<Id, D>, ScheduleError<Id>> {
+    search_with_progress(params, |_| {})
+}
+
+/// Same as `search`, calling `on_progress` after every combination of pools
+/// and every few thousand visited nodes.
+pub fn search_with_progress<Id, D, F>(
+    mut params: EngineParams<Id, D>,
+    on_progress: F,
+) -> Result<SearchOutcome<Id, D>, ScheduleError<Id>>
+where
+    Id: Eq + Clone,
+    D: Clone,
+    F: FnMut(&Progress) + 'static,
+{
+    let ranking = params.ranking.take();
+    let top_k = params.top_k;
+    let max_results = params.max_results.unwrap_or(usize::MAX);
+
+    let mut iter = solve_iter(params)?;
+    iter.on_progress(on_progress);
+
+    let schedules = match (ranking, top_k) {
+        (Some(ranking), Some(k)) => top_k_of(&mut iter, k, max_results, &ranking)
+            .into_iter()
+            .map(|(_, _, schedule)| schedule)
+            .collect(),
+        (Some(ranking), None) => rank(iter.by_ref().take(max_results).collect(), &ranking),
+        (None, k) => iter
+            .by_ref()
+            .take(k.unwrap_or(usize::MAX).min(max_results))
+            .collect(),
+    };
+
+    Ok(SearchOutcome {
+        schedules,
+        exhaustive: iter.is_exhaustive(),
+        progress: iter.progress(),
+        dropped_seeds: iter.dropped_seeds().to_vec(),
+        unavailable_grids: iter.unavailable_grids().to_vec(),
+    })
+}
+
+/// Finds the `k` best schedules according to `scorer`, sorted from best to worst.
+///
+/// Only the `k` best schedules found so far are kept, and any branch whose
+/// `Scorer::bound` can't beat the current k-th best is not descended into.
+pub fn solve_top_k<Id, D, S>(
+    params: EngineParams<Id, D>,
+    k: usize,
+    scorer: &S,
+) -> Result<Vec<Schedule<Id, D>>, ScheduleError<Id>>
+where
+    Id: Eq + Clone,
+    D: Clone,
+    S: Scorer<Id, D> + ?Sized,
+{
+    let mut iter = solve_iter(params)?;
+    Ok(top_k_of(&mut iter, k, usize::MAX, scorer)
+        .into_iter()
+        .map(|(_, _, schedule)| schedule)
+        .collect())
+}
+
+/// Keeps the `k` best out of the first `max_results` schedules `iter` finds,
+/// along with their score and the position of their combination.
+fn top_k_of<Id, D, S>(
+    iter: &mut ScheduleIter<Id, D>,
+    k: usize,
+    max_results: usize,
+    scorer: &S,
+) -> Vec<(f64, usize, Schedule<Id, D>)>
+where
+    Id: Eq + Clone,
+    D: Clone,
+    S: Scorer<Id, D> + ?Sized,
+{
+    if k == 0 {
+        return vec![];
+    }
+
+    // Sorted from best to worst
+    let mut best: Vec<(f64, usize, Schedule<Id, D>)> = Vec::with_capacity(k + 1);
+    let mut found = 0;
+
+    while found < max_results
+        && iter
+            .next_leaf_pruned(|partial| best.len() == k && scorer.bound(partial) >= best[k - 1].0)
+    {
+        found += 1;
+        let score = scorer.score(&iter.schedule);
+        if best.len() == k && score >= best[k - 1].0 {
+            continue;
+        }
+
+        // Ties are kept in the order they were found
+        let position = best.partition_point(|(other, _, _)| *other <= score);
+        best.insert(position, (score, iter.position, iter.schedule.clone()));
+        best.truncate(k);
+    }
+
+    best
+}
+
+/// Lists every pair of seeds that clash, along with where they do. Seeds
+/// that don't overlap clash too when `gap` requires a longer break between them.
+pub fn seed_conflicts<Id: Eq + Clone, D: Clone>(
+    seeds: &[Grid<Id, D>],
+    gap: &GapRule<Id, D>,
+) -> Vec<SeedClash<Id>> {
+    let mut conflicts = vec![];
+
+    for (i, first) in seeds.iter().enumerate() {
+        for (j, second) in seeds.iter().enumerate().skip(i + 1) {
+            let required_minutes = gap.required(first, second);
+            for (day, interval) in first.too_close_to(second, required_minutes) {
+                conflicts.push(SeedClash {
+                    seeds: (i, j),
+                    pools: (first.pool_id.clone(), second.pool_id.clone()),
+                    day,
+                    interval,
+                    required_minutes,
+                });
+            }
+        }
+    }
+
+    conflicts
+}
+
+/// Positions of the seeds to leave out so that the remaining ones are the
+/// largest set within `availability` that can be taken together.
+fn seeds_to_drop<Id: Eq + Clone, D: Clone>(
+    seeds: &[Grid<Id, D>],
+    gap: &GapRule<Id, D>,
+    availability: &Availability,
+) -> Vec<usize> {
+    let available: Vec<bool> = seeds
+        .iter()
+        .map(|seed| availability.permits(seed))
+        .collect();
+    let clashes: Vec<Vec<bool>> = seeds
+        .iter()
+        .map(|first| {
+            seeds
+                .iter()
+                .map(|second| !gap.allows(first, second))
+                .collect()
+        })
+        .collect();
+
+    // Seeds are few, so an exhaustive search is affordable. Taking a seed is
+    // tried before leaving it out, which favours earlier seeds on ties.
+    fn visit(
+        clashes: &[Vec<bool>],
+        available: &[bool],
+        seed: usize,
+        kept: &mut Vec<usize>,
+        best: &mut Vec<usize>,
+    ) {
+        if kept.len() + (clashes.len() - seed) <= best.len() {
+            return;
+        }
+        if seed == clashes.len() {
+            *best = kept.clone();
+            return;
+        }
+
+        if available[seed] && kept.iter().all(|&other| !clashes[seed][other]) {
+            kept.push(seed);
+            visit(clashes, available, seed + 1, kept, best);
+            kept.pop();
+        }
+        visit(clashes, available, seed + 1, kept, best);
+    }
+
+    let mut best = vec![];
+    visit(&clashes, &available, 0, &mut vec![], &mut best);
+
+    (0..seeds.len())
+        .filter(|seed| !best.contains(seed))
+        .collect()
+}
+
+/// Counts the schedules `engine_main` would find, without building any of them.
+///
+/// `ranking`, `top_k` and `max_results` are ignored. Counting stops once `cap`
+/// is reached, or when `max_nodes` or a cancellation stop the search, in which
+/// case the count is only a lower bound. As no schedule is built, the observer
+/// isn't told about pruned branches nor leaves.
+pub fn count_schedules<Id: Eq + Clone, D: Clone>(
+    params: EngineParams<Id, D>,
+    cap: Option<u64>,
+) -> Result<u64, ScheduleError<Id>> {
+    let cap = cap.unwrap_or(u64::MAX);
+    let mut iter = solve_iter(params)?;
+    iter.build = false;
+
+    let mut count = 0;
+    while count < cap && iter.next_leaf() {
+        count += 1;
+    }
+
+    Ok(count)
+}
+
+/// Tells which grids of `pool_list` can be taken together, without running
+/// a full search. `gap` and `travel_times` apply as in `EngineParams`.
+pub fn compatibility_matrix<Id: Eq, D>(
+    pool_list: &[Pool<Id, D>],
+    gap: &GapRule<Id, D>,
+    travel_times: &TravelTimes,
+) -> CompatibilityMatrix {
+    let gap = gap.clone().with_travel_times(travel_times.clone());
+    CompatibilityMatrix::with_gap(pool_list, &gap)
+}
+
+/// Same as `engine_main`, but instead of collecting every valid schedule up front
+/// it returns an iterator that finds them one at a time, so that callers can stop
+/// early or stream results.
+pub fn solve_iter<Id: Eq + Clone, D: Clone>(
+    mut params: EngineParams<Id, D>,
+) -> Result<ScheduleIter<Id, D>, ScheduleError<Id>> {
+    let max_nodes = params.max_nodes.unwrap_or(u64::MAX);
+    let cancel = params.cancel.take();
+    let mut observer = params.observer.take().unwrap_or_else(|| Box::new(()));
+
+    let space = Arc::new(search_space(params, observer.as_mut())?);
+    let combinations = Box::new(space.combinations().enumerate());
+
+    let mut iter = ScheduleIter::new(space, combinations);
+    iter.max_nodes = max_nodes;
+    iter.cancel = cancel;
+    iter.observer = observer;
+    Ok(iter)
+}
+
+/// Validates `params` and merges the seeds.
+fn search_space<Id: Eq + Clone, D: Clone>(
+    params: EngineParams<Id, D>,
+    observer: &mut dyn EngineObserver<Id, D>,
+) -> Result<SearchSpace<Id, D>, ScheduleError<Id>> {
+    let Bound { min, max } = params.bound;
+    if min > max {
+        return Err(ScheduleError::InvalidBound { min, max });
     }
 
     // Check that pools don't have repeated id's
@@ -53,97 +496,780 @@ pub fn engine_main<Id: Eq + Clone + Debug, D: Clone + Debug>(
 
             if pool_inner.pool_id == pool.pool_id {
                 // Found repeated pool, return error
-                bail!("Found repeated pool id. Pool id must be unique for any given pool");
+                return Err(ScheduleError::DuplicatePoolId(pool.pool_id.clone()));
             }
         }
     }
 
+    let gap = params.gap.with_travel_times(params.travel_times);
+    let mut master_schedule = Schedule::with_gap(gap);
+    let dropped_seeds = match params.seed_policy {
+        SeedPolicy::Strict => vec![],
+        SeedPolicy::LargestCompatible => {
+            seeds_to_drop(&params.seeds, master_schedule.gap(), &params.availability)
+        }
+    };
+
     // Check that all seeds are compatible with one another
-    let mut master_schedule = Schedule::<Id, D>::new();
+    for (seed, s) in params.seeds.iter().enumerate() {
+        if dropped_seeds.contains(&seed) {
+            continue;
+        }
+        if let Some(violation) = params.availability.violations(s).into_iter().next() {
+            return Err(ScheduleError::SeedUnavailable {
+                seed,
+                day: violation.day,
+                time_range: violation.time_range,
+            });
+        }
+        if let Err(err) = master_schedule.try_merge(s) {
+            return Err(match err {
+                ScheduleError::Conflict {
+                    existing_pool,
+                    day,
+                    overlap,
+                    ..
+                } => ScheduleError::SeedConflict {
+                    seed,
+                    existing_pool,
+                    day,
+                    overlap,
+                },
+                ScheduleError::InsufficientGap {
+                    existing_pool,
+                    day,
+                    interval,
+                    required_minutes,
+                    ..
+                } => ScheduleError::SeedTooClose {
+                    seed,
+                    existing_pool,
+                    day,
+                    interval,
+                    required_minutes,
+                },
+                err => err,
+            });
+        }
+        observer.seed_merged(seed, s);
+    }
 
-    for s in params.seeds {
-        master_schedule.try_merge(&s)?;
+    // Pools some kept seed belongs to are already part of every schedule
+    let available = master_schedule.len()
+        + params
+            .pool_list
+            .iter()
+            .filter(|pool| !master_schedule.has_pool(&pool.pool_id))
+            .count();
+    // Ranges may reach past what is available, as long as they start within it
+    if min > available {
+        return Err(ScheduleError::BoundTooLarge {
+            bound: min,
+            available,
+        });
     }
+    let max = max.min(available);
 
-    dbg!(&master_schedule);
+    // Seeds take up their slots before any pool does
+    let seeds = master_schedule.len();
+    if seeds > max {
+        return Err(ScheduleError::SeedsExceedBound { seeds, bound: max });
+    }
 
-    // Generate combinations
-    let combinations = params.pool_list.iter().combinations(params.bound);
+    let required = params
+        .pool_list
+        .iter()
+        .filter(|pool| pool.required && !master_schedule.has_pool(&pool.pool_id))
+        .count();
+    if seeds + required > max {
+        return Err(ScheduleError::RequiredExceedsBound {
+            required: seeds + required,
+            bound: max,
+        });
+    }
+
+    let mut space = SearchSpace::new(
+        master_schedule,
+        params.pool_list,
+        Bound { min, max },
+        &params.availability,
+    );
+    space.dropped_seeds = dropped_seeds;
+    Ok(space)
+}
 
-    let mut valid_schedules = vec![];
+/// Same as `search`, but combinations are spread across worker threads.
+///
+/// Each worker takes the next combination that nobody has traversed yet, and
+/// schedules are merged back by the position of their combination. Unless a
+/// limit is reached, results are identical to those of `search` regardless of
+/// how many threads were used. With a ranking and `top_k`, every worker keeps
+/// its own `k` best schedules, pruning as `solve_top_k` does.
+///
+/// `max_results` and `max_nodes` count across workers, which all stop once
+/// either is reached. Which schedules are found by then depends on how the
+/// workers interleave, and `max_nodes` may be overshot by a node per worker.
+/// Cancelling stops every worker. The observer only hears about seeds.
+#[cfg(feature = "parallel")]
+pub fn solve_parallel<Id, D>(
+    mut params: EngineParams<Id, D>,
+) -> Result<SearchOutcome<Id, D>, ScheduleError<Id>>
+where
+    Id: Eq + Clone + Send + Sync,
+    D: Clone + Send + Sync,
+{
+    use std::sync::Mutex;
+    use std::thread;
 
-    for mut c in combinations {
-        stack_main(&mut master_schedule, &mut c, &mut valid_schedules)?;
+    let ranking = params.ranking.take();
+    let top_k = params.top_k;
+    let max_results = params.max_results.unwrap_or(usize::MAX);
+    let cancel = params.cancel.take();
+    let limits = Arc::new(SharedLimits {
+        max_nodes: params.max_nodes.unwrap_or(u64::MAX),
+        // Without a ranking, the first `top_k` schedules are the ones returned
+        max_schedules: match (&ranking, top_k) {
+            (None, Some(k)) => k.min(max_results),
+            _ => max_results,
+        },
+        ..SharedLimits::default()
+    });
+    let mut observer = params.observer.take().unwrap_or_else(|| Box::new(()));
+    let space = Arc::new(search_space(params, observer.as_mut())?);
+    let combinations = Arc::new(Mutex::new(space.combinations().enumerate()));
+    let workers = thread::available_parallelism().map_or(1, |n| n.get());
+
+    let results: Vec<_> = thread::scope(|scope| {
+        let handles: Vec<_> = (0..workers)
+            .map(|_| {
+                scope.spawn(|| {
+                    let combinations = Arc::clone(&combinations);
+                    let next = std::iter::from_fn(move || combinations.lock().unwrap().next());
+                    let mut iter = ScheduleIter::new(Arc::clone(&space), Box::new(next));
+                    iter.cancel = cancel.clone();
+                    iter.shared = Some(Arc::clone(&limits));
+
+                    let found = match (&ranking, top_k) {
+                        (Some(ranking), Some(k)) => top_k_of(&mut iter, k, usize::MAX, ranking),
+                        _ => {
+                            let mut found = vec![];
+                            while iter.next_leaf() {
+                                found.push((0.0, iter.position, iter.schedule.clone()));
+                            }
+                            found
+                        }
+                    };
+                    (iter.progress(), iter.is_exhaustive(), found)
+                })
+            })
+            .collect();
+
+        handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .collect()
+    });
+
+    let mut progress = Progress {
+        combinations_total: space.combination_count(),
+        ..Progress::default()
+    };
+    let mut exhaustive = true;
+    let mut found = vec![];
+    for (worker_progress, worker_exhaustive, schedules) in results {
+        progress.combinations_done += worker_progress.combinations_done;
+        progress.nodes_visited += worker_progress.nodes_visited;
+        exhaustive &= worker_exhaustive;
+        found.extend(schedules);
     }
 
-    Ok(valid_schedules)
+    let schedules = match (ranking, top_k) {
+        (Some(_), Some(k)) => {
+            // Ties go to the earlier combination, as they would in `search`
+            found.sort_by(|(a, i, _), (b, j, _)| a.total_cmp(b).then(i.cmp(j)));
+            found
+                .into_iter()
+                .take(k)
+                .map(|(_, _, schedule)| schedule)
+                .collect()
+        }
+        (ranking, k) => {
+            found.sort_by_key(|(_, position, _)| *position);
+            let schedules = found
+                .into_iter()
+                .map(|(_, _, schedule)| schedule)
+                .take(max_results);
+            match ranking {
+                Some(ranking) => rank(schedules.collect(), &ranking),
+                None => schedules.take(k.unwrap_or(usize::MAX)).collect(),
+            }
+        }
+    };
+
+    Ok(SearchOutcome {
+        schedules,
+        exhaustive,
+        progress,
+        dropped_seeds: space.dropped_seeds.clone(),
+        unavailable_grids: space.unavailable_grids.clone(),
+    })
 }
 
-fn stack_main<K: Eq + PartialEq + Clone + Debug, V: Clone + Debug>(
-    master_schedule: &mut Schedule<K, V>,
-    combination: &mut Vec<&Pool<K, V>>,
-    schedule_list: &mut Vec<Schedule<K, V>>,
-) -> Result<()> {
-    // Suppose we have a combination of pools  or "stack" {A,B,C} such that
-    // A -> [a1, a2, ...]
-    // B -> [b1, b2, ...]
-    // C -> [c1, c2, ...]
-    //
-    // (each level is what is refered to as a "stack level")
-    //
-    // Then all we have to do is find all valid traversals of A->B->C
-    // i.e: { [a1,b2,c2], [a2,b1,c1], ...}
+/// Everything about a search that doesn't change while traversing it.
+struct SearchSpace<Id: Eq + Clone, D: Clone> {
+    pool_list: Vec<Pool<Id, D>>,
+    /// Indices of the pools that start every combination.
+    required: Vec<usize>,
+    /// Indices of the remaining, optional, pools. Pools some seed belongs to
+    /// are in neither list.
+    optional: Vec<usize>,
+    bound: Bound,
+    /// Schedule made of the seeds alone.
+    master_schedule: Schedule<Id, D>,
+    /// Precomputed compatibility between every pair of grids.
+    matrix: CompatibilityMatrix,
+    /// Grids that are compatible with the seeds and the availability.
+    seed_compatible: GridSet,
+    /// Grids that fall outside the availability, as (pool, grid) indices.
+    unavailable_grids: Vec<(usize, usize)>,
+    /// Seeds left out of `master_schedule`.
+    dropped_seeds: Vec<usize>,
+}
 
-    if let Some(current_stack_level) = combination.pop() {
-        for grid in current_stack_level.grid_list.iter() {
-            // Descend down each grid
-            let mut schedule = (*master_schedule).clone();
+impl<Id, D> SearchSpace<Id, D>
+where
+    Id: Eq + Clone,
+    D: Clone,
+{
+    fn new(
+        master_schedule: Schedule<Id, D>,
+        pool_list: Vec<Pool<Id, D>>,
+        bound: Bound,
+        availability: &Availability,
+    ) -> Self {
+        let (required, optional): (Vec<usize>, Vec<usize>) = (0..pool_list.len())
+            .filter(|&i| !master_schedule.has_pool(&pool_list[i].pool_id))
+            .partition(|&i| pool_list[i].required);
 
-            if schedule.try_merge(grid).is_ok() {
-                stack_recursive(combination, &mut schedule, schedule_list);
+        let matrix = CompatibilityMatrix::with_gap(&pool_list, master_schedule.gap());
+        let mut seed_compatible = GridSet::new(matrix.len());
+        let mut unavailable_grids = vec![];
+        for (pool, p) in pool_list.iter().enumerate() {
+            for (i, grid) in p.grid_list.iter().enumerate() {
+                if !availability.permits(grid) {
+                    unavailable_grids.push((pool, i));
+                } else if master_schedule.accepts(grid) {
+                    seed_compatible.insert(matrix.index((pool, i)));
+                }
             }
         }
-    } else {
-        // Sould be unreachable
-        bail!("Empty stack error.");
+
+        SearchSpace {
+            pool_list,
+            required,
+            optional,
+            bound,
+            master_schedule,
+            matrix,
+            seed_compatible,
+            unavailable_grids,
+            dropped_seeds: vec![],
+        }
+    }
+
+    /// Sizes of the combinations of optional pools, as the seeds and the
+    /// required pools take up part of the bound.
+    fn sizes(&self) -> RangeInclusive<usize> {
+        let fixed = self.master_schedule.len() + self.required.len();
+        self.bound.min.saturating_sub(fixed)..=self.bound.max - fixed
+    }
+
+    /// Number of combinations `combinations` yields, saturating at `u64::MAX`.
+    fn combination_count(&self) -> u64 {
+        let n = self.optional.len() as u128;
+
+        let total: u128 = self
+            .sizes()
+            .filter(|&size| size as u128 <= n)
+            .map(|size| {
+                // Each partial product is itself a binomial coefficient, so the
+                // division is always exact.
+                (0..size as u128).fold(1u128, |acc, i| {
+                    (acc.saturating_mul(n - i) / (i + 1)).min(u64::MAX as u128)
+                })
+            })
+            .sum();
+
+        total.min(u64::MAX as u128) as u64
+    }
+
+    /// Every combination of pools to traverse, as indices into `pool_list`.
+    /// All combinations of a given size come before those of the next size.
+    fn combinations(&self) -> impl Iterator<Item = Vec<usize>> + Send + 'static {
+        let required = self.required.clone();
+        let optional = self.optional.clone();
+
+        self.sizes()
+            .flat_map(move |size| optional.clone().into_iter().combinations(size))
+            .map(move |combination| [required.clone(), combination].concat())
+    }
+}
+
+type ProgressCallback = Box<dyn FnMut(&Progress)>;
+
+/// Limits shared by several traversals, each over its own combinations,
+/// such as the workers of `solve_parallel`.
+#[derive(Debug, Default)]
+struct SharedLimits {
+    /// Partial schedules visited by every traversal.
+    nodes: AtomicU64,
+    max_nodes: u64,
+    /// Schedules found by every traversal.
+    schedules: AtomicUsize,
+    max_schedules: usize,
+    /// Set once a limit is reached, so that every traversal stops.
+    stop: AtomicBool,
+}
+
+impl SharedLimits {
+    fn node_visited(&self) {
+        if self.nodes.fetch_add(1, Ordering::Relaxed) + 1 >= self.max_nodes {
+            self.stop.store(true, Ordering::Relaxed);
+        }
+    }
+
+    fn schedule_found(&self) {
+        if self.schedules.fetch_add(1, Ordering::Relaxed) + 1 >= self.max_schedules {
+            self.stop.store(true, Ordering::Relaxed);
+        }
+    }
+
+    fn is_reached(&self) -> bool {
+        self.stop.load(Ordering::Relaxed)
     }
+}
 
-    Ok(())
+/// Depth first traversal over every combination of pools, yielding each valid
+/// schedule as soon as it is found.
+///
+/// Suppose we have a combination of pools  or "stack" {A,B,C} such that
+/// A -> [a1, a2, ...]
+/// B -> [b1, b2, ...]
+/// C -> [c1, c2, ...]
+///
+/// (each level is what is refered to as a "stack level")
+///
+/// Then all we have to do is find all valid traversals of A->B->C
+/// i.e: { [a1,b2,c2], [a2,b1,c1], ...}
+///
+/// Instead of recursing, the traversal state is kept explicitly so that it can
+/// be suspended every time a valid schedule is found.
+pub struct ScheduleIter<Id: Eq + Clone, D: Clone> {
+    space: Arc<SearchSpace<Id, D>>,
+    /// Combinations that are still to be traversed, along with their position
+    /// among every combination of the search.
+    combinations: Box<dyn Iterator<Item = (usize, Vec<usize>)> + Send>,
+    /// Combination currently being traversed, as indices into `pool_list`.
+    stack: Vec<usize>,
+    /// Position of the combination currently being traversed.
+    position: usize,
+    /// Index of the next grid to try on each stack level that has been reached.
+    cursors: Vec<usize>,
+    /// Seeds plus one grid for every stack level before the current one.
+    schedule: Schedule<Id, D>,
+    /// Whether grids are pushed onto `schedule` at all. Counting schedules only
+    /// needs `cursors` and `allowed`, which spares cloning every grid visited.
+    build: bool,
+    /// Whether the last grid of `schedule` belongs to a schedule that was already yielded.
+    at_leaf: bool,
+    /// `allowed[i]` holds the grids that are compatible with the seeds and with
+    /// the grids picked on every stack level before `i`.
+    allowed: Vec<GridSet>,
+    /// Number of partial schedules visited so far.
+    nodes: u64,
+    /// The traversal stops once `nodes` reaches this.
+    max_nodes: u64,
+    cancel: Option<CancelToken>,
+    /// Limits this traversal shares with others running alongside it.
+    shared: Option<Arc<SharedLimits>>,
+    /// Whether every combination has been traversed.
+    exhausted: bool,
+    combinations_done: u64,
+    combinations_total: u64,
+    on_progress: Option<ProgressCallback>,
+    observer: Box<dyn EngineObserver<Id, D>>,
 }
 
-fn stack_recursive<K: Eq + PartialEq + Clone + ToOwned + Debug, V: Clone + ToOwned + Debug>(
-    combination: &mut Vec<&Pool<K, V>>,
-    schedule: &mut Schedule<K, V>,
-    schedule_list: &mut Vec<Schedule<K, V>>,
-) -> Option<()> {
-    if let Some(current_stack_level) = combination.pop() {
-        for grid in current_stack_level.grid_list.iter() {
-            // Depth first
-            if schedule.try_merge(grid).is_ok() {
-                stack_recursive(combination, schedule, schedule_list);
-                // Trim last added Grid (because schedule.try_merge() was Ok())
-                // and continue iteration.
-                schedule.remove_last_added();
+impl<Id, D> ScheduleIter<Id, D>
+where
+    Id: Eq + Clone,
+    D: Clone,
+{
+    fn new(
+        space: Arc<SearchSpace<Id, D>>,
+        combinations: Box<dyn Iterator<Item = (usize, Vec<usize>)> + Send>,
+    ) -> Self {
+        ScheduleIter {
+            combinations,
+            stack: vec![],
+            position: 0,
+            cursors: vec![],
+            schedule: space.master_schedule.clone(),
+            build: true,
+            at_leaf: false,
+            allowed: vec![space.seed_compatible.clone(); space.bound.max + 1],
+            nodes: 0,
+            max_nodes: u64::MAX,
+            cancel: None,
+            shared: None,
+            exhausted: false,
+            combinations_done: 0,
+            combinations_total: space.combination_count(),
+            on_progress: None,
+            observer: Box::new(()),
+            space,
+        }
+    }
+
+    /// Number of partial schedules, complete ones included, visited so far.
+    pub fn nodes_visited(&self) -> u64 {
+        self.nodes
+    }
+
+    /// Positions of the seeds left out because of `SeedPolicy::LargestCompatible`.
+    pub fn dropped_seeds(&self) -> &[usize] {
+        &self.space.dropped_seeds
+    }
+
+    /// Grids left out because of `EngineParams::availability`, as (pool, grid)
+    /// indices into `EngineParams::pool_list`.
+    pub fn unavailable_grids(&self) -> &[(usize, usize)] {
+        &self.space.unavailable_grids
+    }
+
+    /// Whether the traversal has gone over every combination, as opposed to
+    /// not having finished yet or having been stopped by a limit.
+    pub fn is_exhaustive(&self) -> bool {
+        self.exhausted
+    }
+
+    pub fn progress(&self) -> Progress {
+        Progress {
+            combinations_done: self.combinations_done,
+            combinations_total: self.combinations_total,
+            nodes_visited: self.nodes,
+        }
+    }
+
+    /// Calls `on_progress` after every combination and every few thousand nodes.
+    pub fn on_progress<F: FnMut(&Progress) + 'static>(&mut self, on_progress: F) {
+        self.on_progress = Some(Box::new(on_progress));
+    }
+
+    fn report_progress(&mut self) {
+        let progress = self.progress();
+        if let Some(on_progress) = self.on_progress.as_mut() {
+            on_progress(&progress);
+        }
+    }
+
+    fn combination_done(&mut self) {
+        self.combinations_done += 1;
+        self.report_progress();
+    }
+
+    /// Whether `max_nodes` or a shared limit has been reached, or the search
+    /// has been cancelled.
+    fn should_stop(&self) -> bool {
+        self.nodes >= self.max_nodes
+            || self
+                .cancel
+                .as_ref()
+                .is_some_and(|cancel| cancel.is_cancelled())
+            || self
+                .shared
+                .as_ref()
+                .is_some_and(|shared| shared.is_reached())
+    }
+
+    /// Tells the shared limits, if any, about a complete schedule.
+    fn found_leaf(&mut self) {
+        if let Some(shared) = &self.shared {
+            shared.schedule_found();
+        }
+    }
+
+    /// Forward checking for the stack levels from `depth` onwards: moves the pool
+    /// with the fewest allowed grids to `depth`, so that the most constrained pool
+    /// is descended into first.
+    /// Returns false when some pool has no allowed grid left, as there is no
+    /// point in descending any further.
+    fn order_remaining(&mut self, depth: usize) -> bool {
+        let allowed = &self.allowed[depth];
+        let mut most_constrained: Option<(usize, usize)> = None;
+
+        for level in depth..self.stack.len() {
+            let domain = allowed.count_in(self.space.matrix.grids_of(self.stack[level]));
+            if domain == 0 {
+                return false;
+            }
+            if most_constrained.is_none_or(|(smallest, _)| domain < smallest) {
+                most_constrained = Some((domain, level));
             }
         }
 
-        // Push back stack level
-        combination.push(current_stack_level);
-        return None;
-    } else {
-        // Reached the end of the stack, start unwinding
-        schedule_list.push(schedule.to_owned());
-        return Some(());
+        if let Some((_, level)) = most_constrained {
+            self.stack.swap(depth, level);
+        }
+        true
+    }
+
+    /// Advances the traversal until `self.schedule` holds the next valid schedule.
+    /// Returns false once every combination has been exhausted.
+    fn next_leaf(&mut self) -> bool {
+        self.next_leaf_pruned(|_| false)
+    }
+
+    /// Same as `next_leaf`, but does not descend into a partial schedule
+    /// whenever `prune` returns true for it.
+    fn next_leaf_pruned<F>(&mut self, mut prune: F) -> bool
+    where
+        F: FnMut(&Schedule<Id, D>) -> bool,
+    {
+        if self.at_leaf {
+            // Trim the grid that completed the previous schedule and keep iterating
+            // over its stack level.
+            self.pop_grid();
+            self.at_leaf = false;
+        }
+
+        loop {
+            if self.should_stop() {
+                return false;
+            }
+
+            if self.cursors.is_empty() {
+                match self.combinations.next() {
+                    Some((position, combination)) => {
+                        self.position = position;
+                        self.stack = combination;
+                    }
+                    None => {
+                        self.exhausted = true;
+                        return false;
+                    }
+                }
+                self.observer.combination(&self.stack);
+
+                if self.stack.is_empty() {
+                    // Nothing to descend into, the seeds alone are the schedule.
+                    self.combination_done();
+                    self.found_leaf();
+                    self.observer.leaf(&self.schedule);
+                    return true;
+                }
+
+                if !self.order_remaining(0) {
+                    // Some pool has no group compatible with the seeds
+                    self.combination_done();
+                    continue;
+                }
+
+                self.cursors.push(0);
+            }
+
+            let depth = self.cursors.len() - 1;
+            let pool = self.stack[depth];
+            let cursor = self.cursors[depth];
+
+            if let Some(grid) = self.space.pool_list[pool].grid_list.get(cursor) {
+                self.cursors[depth] += 1;
+
+                // Depth first
+                let index = self.space.matrix.index((pool, cursor));
+                if self.allowed[depth].contains(index) {
+                    if self.build {
+                        self.schedule.push(grid.clone());
+                    }
+                    self.nodes += 1;
+                    if let Some(shared) = &self.shared {
+                        shared.node_visited();
+                    }
+                    if self.nodes.is_multiple_of(PROGRESS_INTERVAL) {
+                        self.report_progress();
+                    }
+
+                    if self.build && prune(&self.schedule) {
+                        self.observer.pruned(&self.schedule);
+                        self.pop_grid();
+                        continue;
+                    }
+
+                    if depth + 1 == self.stack.len() {
+                        // Reached the end of the stack
+                        if self.build {
+                            self.observer.leaf(&self.schedule);
+                        }
+                        self.found_leaf();
+                        self.at_leaf = true;
+                        return true;
+                    }
+
+                    // Whatever comes next must also be compatible with this grid
+                    let (previous, next) = self.allowed.split_at_mut(depth + 1);
+                    next[0].assign_intersection(&previous[depth], self.space.matrix.row(index));
+
+                    if !self.order_remaining(depth + 1) {
+                        if self.build {
+                            self.observer.pruned(&self.schedule);
+                        }
+                        self.pop_grid();
+                        continue;
+                    }
+                    self.cursors.push(0);
+                }
+            } else {
+                // Stack level exhausted, unwind to the previous one
+                self.cursors.pop();
+                if self.cursors.is_empty() {
+                    self.combination_done();
+                } else {
+                    self.pop_grid();
+                }
+            }
+        }
+    }
+
+    /// Takes the grid of the deepest stack level out of `schedule`.
+    fn pop_grid(&mut self) {
+        if self.build {
+            self.schedule.remove_last_added();
+        }
+    }
+}
+
+impl<Id, D> Iterator for ScheduleIter<Id, D>
+where
+    Id: Eq + Clone,
+    D: Clone,
+{
+    type Item = Schedule<Id, D>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.next_leaf() {
+            Some(self.schedule.clone())
+        } else {
+            None
+        }
+    }
+}
+
+/// Schedules found by a single `Solver::step`.
+#[derive(Debug, Serialize)]
+pub struct StepResult<Id: Eq + Clone, D: Clone> {
+    /// Schedules found during this step, in traversal order.
+    pub schedules: Vec<Schedule<Id, D>>,
+    /// Whether the search is over, so that further steps won't find anything.
+    pub done: bool,
+    /// Same as `SearchOutcome::exhaustive`.
+    pub exhaustive: bool,
+    pub progress: Progress,
+}
+
+/// Search that runs in chunks, for callers that can't block until every
+/// schedule has been found, like the browser's event loop.
+///
+/// Each call to `step` resumes the traversal where the previous one left off.
+/// Schedules are handed out in traversal order as they are found, so `ranking`
+/// is not applied, while `top_k` works like `max_results`.
+pub struct Solver<Id: Eq + Clone, D: Clone> {
+    iter: ScheduleIter<Id, D>,
+    /// Node budget for the whole search, as opposed to a single step.
+    max_nodes: u64,
+    max_results: usize,
+    found: usize,
+    done: bool,
+}
+
+impl<Id, D> Solver<Id, D>
+where
+    Id: Eq + Clone,
+    D: Clone,
+{
+    pub fn new(params: EngineParams<Id, D>) -> Result<Self, ScheduleError<Id>> {
+        let max_results = params
+            .max_results
+            .unwrap_or(usize::MAX)
+            .min(params.top_k.unwrap_or(usize::MAX));
+        let iter = solve_iter(params)?;
+
+        Ok(Solver {
+            max_nodes: iter.max_nodes,
+            iter,
+            max_results,
+            found: 0,
+            done: false,
+        })
+    }
+
+    /// Advances the search by visiting at most `max_nodes` partial schedules.
+    pub fn step(&mut self, max_nodes: u64) -> StepResult<Id, D> {
+        let mut schedules = vec![];
+
+        if !self.done {
+            self.iter.max_nodes = self
+                .max_nodes
+                .min(self.iter.nodes.saturating_add(max_nodes));
+
+            while self.found < self.max_results && self.iter.next_leaf() {
+                schedules.push(self.iter.schedule.clone());
+                self.found += 1;
+            }
+
+            self.done = self.iter.is_exhaustive()
+                || self.found >= self.max_results
+                || self.iter.nodes >= self.max_nodes
+                || self
+                    .iter
+                    .cancel
+                    .as_ref()
+                    .is_some_and(|cancel| cancel.is_cancelled());
+        }
+
+        StepResult {
+            schedules,
+            done: self.done,
+            exhaustive: self.iter.is_exhaustive(),
+            progress: self.iter.progress(),
+        }
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.done
+    }
+
+    pub fn progress(&self) -> Progress {
+        self.iter.progress()
+    }
+
+    /// Positions of the seeds left out because of `SeedPolicy::LargestCompatible`.
+    pub fn dropped_seeds(&self) -> &[usize] {
+        self.iter.dropped_seeds()
+    }
+
+    /// Grids left out because of `EngineParams::availability`.
+    pub fn unavailable_grids(&self) -> &[(usize, usize)] {
+        self.iter.unavailable_grids()
     }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::utils::*;
+    use chrono::NaiveTime;
 
-    #[test]
-    fn test_engine() {
-        // let schedule = Schedule::new();
+    fn fixture_params() -> EngineParams<u32, u32> {
         let vec_a1 = [
             "19:00".to_string(),
             "20:30".to_string(),
@@ -247,11 +1373,12 @@ mod test {
         pool_b.push(grid_b2);
         pool_b.push(grid_b3);
 
-        let params = EngineParams {
-            seeds: vec![],
-            bound: 2,
-            pool_list: vec![pool_a, pool_b],
-        };
+        EngineParams::new(vec![pool_a, pool_b], 2)
+    }
+
+    #[test]
+    fn test_engine() {
+        let params = fixture_params();
 
         let result = engine_main(params);
 
@@ -259,4 +1386,841 @@ mod test {
 
         assert_eq!(schedule_list.len(), 6);
     }
+
+    #[test]
+    fn test_solve_iter() {
+        let mut iter = solve_iter(fixture_params()).unwrap();
+
+        let first_two: Vec<_> = iter.by_ref().take(2).collect();
+        assert_eq!(first_two.len(), 2);
+        assert!(first_two.iter().all(|schedule| schedule.len() == 2));
+
+        // Resuming picks up where the traversal stopped
+        assert_eq!(iter.count(), 4);
+    }
+
+    #[test]
+    fn test_engine_errors() {
+        let mut params = fixture_params();
+        params.bound = 3.into();
+        assert_eq!(
+            engine_main(params).unwrap_err(),
+            ScheduleError::BoundTooLarge {
+                bound: 3,
+                available: 2
+            }
+        );
+
+        let mut params = fixture_params();
+        params.pool_list.push(Pool::new(2));
+        assert_eq!(
+            engine_main(params).unwrap_err(),
+            ScheduleError::DuplicatePoolId(2)
+        );
+
+        // A grid always clashes with itself
+        let mut params = fixture_params();
+        let pool_b = params.pool_list.pop().unwrap();
+        params.seeds = vec![pool_b.grid_list[0].clone(), pool_b.grid_list[0].clone()];
+        params.bound = 1.into();
+        assert!(matches!(
+            engine_main(params),
+            Err(ScheduleError::SeedConflict {
+                seed: 1,
+                existing_pool: 2,
+                day: Day::MONDAY,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_engine_ranking() {
+        let ranking = Weighted::new(vec![
+            (Criterion::DaysOnCampus, 60.0),
+            (Criterion::IdleTime, 1.0),
+        ]);
+
+        let mut params = fixture_params();
+        params.ranking = Some(ranking.clone());
+
+        let schedule_list = engine_main(params).unwrap();
+        assert_eq!(schedule_list.len(), 6);
+
+        let scores: Vec<_> = schedule_list.iter().map(|s| ranking.score(s)).collect();
+        assert!(scores.windows(2).all(|pair| pair[0] <= pair[1]));
+
+        // a1 and b3 only meet on Monday and Wednesday, but with ten hours
+        // in between classes on each of those days.
+        let mut worst: Vec<_> = schedule_list[5]
+            .grids()
+            .iter()
+            .map(|grid| (grid.pool_id, *grid.data()))
+            .collect();
+        worst.sort();
+        assert_eq!(worst, vec![(1, 1), (2, 3)]);
+        assert_eq!(scores[5], 2.0 * 60.0 + 2.0 * 600.0);
+    }
+
+    #[test]
+    fn test_engine_top_k() {
+        let ranking = Weighted::new(vec![
+            (Criterion::DaysOnCampus, 60.0),
+            (Criterion::Compactness, 1.0),
+        ]);
+
+        let mut params = fixture_params();
+        params.ranking = Some(ranking.clone());
+        let all = engine_main(params).unwrap();
+
+        for k in 0..=7 {
+            let mut params = fixture_params();
+            params.ranking = Some(ranking.clone());
+            params.top_k = Some(k);
+
+            let best: Vec<_> = engine_main(params)
+                .unwrap()
+                .iter()
+                .map(|s| ranking.score(s))
+                .collect();
+            let expected: Vec<_> = all.iter().take(k).map(|s| ranking.score(s)).collect();
+
+            assert_eq!(best, expected);
+        }
+
+        // Without ranking, the first k schedules in traversal order are returned
+        let mut params = fixture_params();
+        params.top_k = Some(4);
+        assert_eq!(engine_main(params).unwrap().len(), 4);
+    }
+
+    #[test]
+    fn test_required_pools() {
+        let mut params = fixture_params();
+        params.bound = 1.into();
+        assert_eq!(engine_main(params).unwrap().len(), 5);
+
+        // Only pool b's groups are left
+        let mut params = fixture_params();
+        params.bound = 1.into();
+        params.pool_list[1].required = true;
+        let schedule_list = engine_main(params).unwrap();
+        assert_eq!(schedule_list.len(), 3);
+        assert!(schedule_list
+            .iter()
+            .all(|schedule| schedule.grids()[0].pool_id == 2));
+
+        let mut params = fixture_params();
+        params.bound = 1.into();
+        params.pool_list[0].required = true;
+        params.pool_list[1].required = true;
+        assert_eq!(
+            engine_main(params).unwrap_err(),
+            ScheduleError::RequiredExceedsBound {
+                required: 2,
+                bound: 1
+            }
+        );
+    }
+
+    #[test]
+    fn test_range_bound() {
+        let mut params = fixture_params();
+        params.bound = (1..=2).into();
+        let schedule_list = engine_main(params).unwrap();
+
+        // 5 schedules with a single pool, then the 6 with both
+        assert_eq!(schedule_list.len(), 11);
+        assert!(schedule_list[..5].iter().all(|s| s.pool_count() == 1));
+        assert!(schedule_list[5..].iter().all(|s| s.pool_count() == 2));
+
+        assert_eq!(serde_json::from_str::<Bound>("3").unwrap(), 3.into());
+        assert_eq!(
+            serde_json::from_str::<Bound>(r#"{ "min": 4, "max": 6 }"#).unwrap(),
+            (4..=6).into()
+        );
+
+        // Only two pools are available, so larger sizes are left out
+        let mut params = fixture_params();
+        params.bound = (2..=4).into();
+        assert_eq!(engine_main(params).unwrap().len(), 6);
+
+        let mut params = fixture_params();
+        params.bound = (3..=4).into();
+        assert_eq!(
+            engine_main(params).unwrap_err(),
+            ScheduleError::BoundTooLarge {
+                bound: 3,
+                available: 2
+            }
+        );
+
+        let mut params = fixture_params();
+        params.bound = Bound { min: 2, max: 1 };
+        assert_eq!(
+            engine_main(params).unwrap_err(),
+            ScheduleError::InvalidBound { min: 2, max: 1 }
+        );
+    }
+
+    #[test]
+    fn test_search_limits() {
+        let outcome = search(fixture_params()).unwrap();
+        assert_eq!(outcome.schedules.len(), 6);
+        assert!(outcome.exhaustive);
+
+        let mut params = fixture_params();
+        params.max_results = Some(4);
+        let outcome = search(params).unwrap();
+        assert_eq!(outcome.schedules.len(), 4);
+        assert!(!outcome.exhaustive);
+
+        // The first two nodes are a1 alone, then a1 + b1
+        let mut params = fixture_params();
+        params.max_nodes = Some(2);
+        let outcome = search(params).unwrap();
+        assert_eq!(outcome.schedules.len(), 1);
+        assert_eq!(outcome.progress.nodes_visited, 2);
+        assert!(!outcome.exhaustive);
+
+        let cancel = CancelToken::new();
+        cancel.cancel();
+        let mut params = fixture_params();
+        params.cancel = Some(cancel);
+        let outcome = search(params).unwrap();
+        assert!(outcome.schedules.is_empty());
+        assert!(!outcome.exhaustive);
+    }
+
+    #[test]
+    fn test_search_progress() {
+        use std::cell::RefCell;
+        use std::rc::Rc;
+
+        let reports = Rc::new(RefCell::new(vec![]));
+        let mut params = fixture_params();
+        params.bound = (1..=2).into();
+
+        let outcome = search_with_progress(params, {
+            let reports = Rc::clone(&reports);
+            move |progress| reports.borrow_mut().push(*progress)
+        })
+        .unwrap();
+
+        let reports = reports.borrow();
+        let done: Vec<_> = reports.iter().map(|p| p.combinations_done).collect();
+        assert_eq!(done, vec![1, 2, 3]);
+        assert!(reports.iter().all(|p| p.combinations_total == 3));
+        assert_eq!(outcome.progress, reports[2]);
+        assert!(outcome.exhaustive);
+
+        // Cancelling from the callback stops the search at the next node
+        let cancel = CancelToken::new();
+        let mut params = fixture_params();
+        params.bound = (1..=2).into();
+        params.cancel = Some(cancel.clone());
+        let outcome = search_with_progress(params, move |_| cancel.cancel()).unwrap();
+        assert_eq!(outcome.schedules.len(), 2);
+        assert_eq!(outcome.progress.combinations_done, 1);
+        assert!(!outcome.exhaustive);
+    }
+
+    #[test]
+    fn test_solver_steps() {
+        let mut params = fixture_params();
+        params.bound = (1..=2).into();
+        let expected = engine_main(params).unwrap();
+
+        let mut params = fixture_params();
+        params.bound = (1..=2).into();
+        let mut solver = Solver::new(params).unwrap();
+
+        let mut found = vec![];
+        let mut steps = 0;
+        while !solver.is_done() {
+            let step = solver.step(3);
+            assert!(step.progress.nodes_visited <= 3 * (steps + 1));
+            found.extend(step.schedules);
+            steps += 1;
+        }
+
+        assert!(steps > 1);
+        assert!(solver.step(3).schedules.is_empty());
+        assert!(solver.step(3).exhaustive);
+
+        let key = |schedule: &Schedule<u32, u32>| -> Vec<u32> {
+            schedule.grids().iter().map(|grid| *grid.data()).collect()
+        };
+        assert_eq!(
+            found.iter().map(key).collect::<Vec<_>>(),
+            expected.iter().map(key).collect::<Vec<_>>()
+        );
+
+        // Limits for the whole search still apply
+        let mut params = fixture_params();
+        params.max_results = Some(4);
+        let mut solver = Solver::new(params).unwrap();
+        assert_eq!(solver.step(2).schedules.len(), 1);
+        let step = solver.step(u64::MAX);
+        assert_eq!(step.schedules.len(), 3);
+        assert!(step.done);
+        assert!(!step.exhaustive);
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn test_solve_parallel() {
+        let key = |schedule_list: Vec<Schedule<u32, u32>>| -> Vec<Vec<(u32, u32)>> {
+            schedule_list
+                .iter()
+                .map(|schedule| {
+                    schedule
+                        .grids()
+                        .iter()
+                        .map(|grid| (grid.pool_id, *grid.data()))
+                        .collect()
+                })
+                .collect()
+        };
+
+        let mut params = fixture_params();
+        params.bound = (1..=2).into();
+        let sequential = search(params).unwrap();
+
+        let mut params = fixture_params();
+        params.bound = (1..=2).into();
+        let outcome = solve_parallel(params).unwrap();
+        assert_eq!(key(outcome.schedules), key(sequential.schedules));
+        assert!(outcome.exhaustive);
+        assert_eq!(outcome.progress, sequential.progress);
+
+        let ranking = Weighted::new(vec![(Criterion::DaysOnCampus, 1.0)]);
+        let mut params = fixture_params();
+        params.ranking = Some(ranking.clone());
+        params.top_k = Some(3);
+        let sequential = key(engine_main(params).unwrap());
+
+        let mut params = fixture_params();
+        params.ranking = Some(ranking);
+        params.top_k = Some(3);
+        let outcome = solve_parallel(params).unwrap();
+        assert_eq!(key(outcome.schedules), sequential);
+        assert!(outcome.exhaustive);
+
+        // Limits stop every worker
+        let mut params = fixture_params();
+        params.max_results = Some(4);
+        let outcome = solve_parallel(params).unwrap();
+        assert_eq!(outcome.schedules.len(), 4);
+        assert!(!outcome.exhaustive);
+
+        let mut params = fixture_params();
+        params.top_k = Some(1);
+        let outcome = solve_parallel(params).unwrap();
+        assert_eq!(outcome.schedules.len(), 1);
+        assert!(!outcome.exhaustive);
+
+        // There is a single combination, so a single worker visits nodes
+        let mut params = fixture_params();
+        params.max_nodes = Some(2);
+        let outcome = solve_parallel(params).unwrap();
+        assert_eq!(outcome.schedules.len(), 1);
+        assert_eq!(outcome.progress.nodes_visited, 2);
+        assert!(!outcome.exhaustive);
+
+        let cancel = CancelToken::new();
+        cancel.cancel();
+        let mut params = fixture_params();
+        params.cancel = Some(cancel);
+        let outcome = solve_parallel(params).unwrap();
+        assert!(outcome.schedules.is_empty());
+        assert!(!outcome.exhaustive);
+    }
+
+    #[test]
+    fn test_count_schedules() {
+        assert_eq!(count_schedules(fixture_params(), None).unwrap(), 6);
+        assert_eq!(count_schedules(fixture_params(), Some(4)).unwrap(), 4);
+
+        let mut params = fixture_params();
+        params.bound = (1..=2).into();
+        params.top_k = Some(1);
+        assert_eq!(count_schedules(params, None).unwrap(), 11);
+
+        // Grid data is never cloned while counting
+        #[derive(Debug, PartialEq)]
+        struct Data(u32);
+        static CLONES: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+        impl Clone for Data {
+            fn clone(&self) -> Self {
+                CLONES.fetch_add(1, Ordering::Relaxed);
+                Data(self.0)
+            }
+        }
+
+        let params = fixture_params();
+        let mut pool_list = vec![];
+        for pool in params.pool_list {
+            let mut counted = Pool::new(pool.pool_id);
+            for grid in pool.grid_list {
+                let data = Data(*grid.data());
+                counted.push(Grid::new(grid.pool_id, grid.time_values, data).unwrap());
+            }
+            pool_list.push(counted);
+        }
+        let params = EngineParams::new(pool_list, 2);
+        assert_eq!(count_schedules(params, None).unwrap(), 6);
+        assert_eq!(CLONES.load(Ordering::Relaxed), 0);
+
+        let mut params = fixture_params();
+        params.bound = 3.into();
+        assert_eq!(
+            count_schedules(params, None).unwrap_err(),
+            ScheduleError::BoundTooLarge {
+                bound: 3,
+                available: 2
+            }
+        );
+    }
+
+    #[test]
+    fn test_observer() {
+        use crate::observer::EngineObserver;
+        use std::cell::RefCell;
+        use std::rc::Rc;
+
+        #[derive(Default)]
+        struct Recorder {
+            seeds: usize,
+            combinations: Vec<Vec<usize>>,
+            pruned: usize,
+            leaves: usize,
+        }
+
+        impl EngineObserver<u32, u32> for Rc<RefCell<Recorder>> {
+            fn seed_merged(&mut self, _index: usize, _seed: &Grid<u32, u32>) {
+                self.borrow_mut().seeds += 1;
+            }
+
+            fn combination(&mut self, pools: &[usize]) {
+                self.borrow_mut().combinations.push(pools.to_vec());
+            }
+
+            fn pruned(&mut self, _partial: &Schedule<u32, u32>) {
+                self.borrow_mut().pruned += 1;
+            }
+
+            fn leaf(&mut self, _schedule: &Schedule<u32, u32>) {
+                self.borrow_mut().leaves += 1;
+            }
+        }
+
+        let recorder = Rc::new(RefCell::new(Recorder::default()));
+        let mut params = fixture_params();
+        params.bound = (1..=2).into();
+        params.observer = Some(Box::new(Rc::clone(&recorder)));
+        engine_main(params).unwrap();
+
+        let recorder = recorder.borrow();
+        assert_eq!(recorder.seeds, 0);
+        assert_eq!(recorder.combinations, vec![vec![0], vec![1], vec![0, 1]]);
+        assert_eq!(recorder.pruned, 0);
+        assert_eq!(recorder.leaves, 11);
+    }
+
+    #[test]
+    fn test_seed_conflicts() {
+        // The first seed clashes with the other two, which are compatible
+        let seeds = vec![
+            Grid::from_vec(10, monday("08:00", "12:00"), "%H:%M", 0).unwrap(),
+            Grid::from_vec(11, monday("09:00", "10:00"), "%H:%M", 0).unwrap(),
+            Grid::from_vec(12, monday("11:00", "13:00"), "%H:%M", 0).unwrap(),
+        ];
+
+        let conflicts = seed_conflicts(&seeds, &GapRule::default());
+        assert_eq!(
+            conflicts
+                .iter()
+                .map(|clash| (clash.seeds, clash.pools, clash.interval))
+                .collect::<Vec<_>>(),
+            vec![
+                ((0, 1), (10, 11), (time("09:00"), time("10:00"))),
+                ((0, 2), (10, 12), (time("11:00"), time("12:00"))),
+            ]
+        );
+        assert!(conflicts.iter().all(|clash| clash.day == Day::MONDAY));
+
+        let mut params = fixture_params();
+        params.seeds = seeds.clone();
+        assert!(matches!(
+            engine_main(params).unwrap_err(),
+            ScheduleError::SeedConflict {
+                seed: 1,
+                existing_pool: 10,
+                ..
+            }
+        ));
+
+        let mut params = fixture_params();
+        params.seeds = seeds;
+        params.bound = 4.into();
+        params.seed_policy = SeedPolicy::LargestCompatible;
+        let outcome = search(params).unwrap();
+        assert_eq!(outcome.dropped_seeds, vec![0]);
+        assert_eq!(outcome.schedules.len(), 6);
+        assert!(outcome
+            .schedules
+            .iter()
+            .all(|schedule| schedule.grids()[..2]
+                .iter()
+                .map(|grid| grid.pool_id)
+                .eq([11, 12])));
+
+        // Ties go to the earlier seeds
+        let seeds = vec![
+            Grid::from_vec(10, monday("08:00", "09:00"), "%H:%M", 0).unwrap(),
+            Grid::from_vec(11, monday("08:30", "09:30"), "%H:%M", 0).unwrap(),
+        ];
+        assert_eq!(
+            seeds_to_drop(&seeds, &GapRule::default(), &Availability::default()),
+            vec![1]
+        );
+
+        // The dropped seed no longer counts toward what is available
+        let mut params = fixture_params();
+        params.seeds = seeds.clone();
+        params.bound = 4.into();
+        params.seed_policy = SeedPolicy::LargestCompatible;
+        assert_eq!(
+            search(params).unwrap_err(),
+            ScheduleError::BoundTooLarge {
+                bound: 4,
+                available: 3
+            }
+        );
+
+        // Seeds dropped for being too close to each other are reported too
+        let seeds = vec![
+            Grid::from_vec(10, monday("08:00", "09:00"), "%H:%M", 0).unwrap(),
+            Grid::from_vec(11, monday("09:10", "10:00"), "%H:%M", 0).unwrap(),
+        ];
+        assert!(seed_conflicts(&seeds, &GapRule::default()).is_empty());
+        let gap = GapRule::new(15);
+        assert_eq!(
+            seeds_to_drop(&seeds, &gap, &Availability::default()),
+            vec![1]
+        );
+        assert_eq!(
+            seed_conflicts(&seeds, &gap),
+            vec![SeedClash {
+                seeds: (0, 1),
+                pools: (10, 11),
+                day: Day::MONDAY,
+                interval: (time("09:00"), time("09:10")),
+                required_minutes: 15,
+            }]
+        );
+
+        let mut params = fixture_params();
+        params.seeds = seeds;
+        params.bound = 4.into();
+        params.gap = gap;
+        assert_eq!(
+            engine_main(params).unwrap_err(),
+            ScheduleError::SeedTooClose {
+                seed: 1,
+                existing_pool: 10,
+                day: Day::MONDAY,
+                interval: (time("09:00"), time("09:10")),
+                required_minutes: 15,
+            }
+        );
+    }
+
+    #[test]
+    fn test_seeds_take_up_bound() {
+        let seed = fixture_params().pool_list[0].grids()[0].clone();
+
+        // Pool a is taken by the seed, so only one of b's groups is added to it
+        let mut params = fixture_params();
+        params.seeds = vec![seed.clone()];
+        let schedule_list = engine_main(params).unwrap();
+        assert_eq!(schedule_list.len(), 3);
+        assert!(schedule_list.iter().all(|schedule| {
+            let pools: Vec<_> = schedule.grids().iter().map(|grid| grid.pool_id).collect();
+            pools == vec![1, 2]
+        }));
+
+        // The seed alone, then the seed with each of b's groups
+        let mut params = fixture_params();
+        params.seeds = vec![seed.clone()];
+        params.bound = (1..=2).into();
+        let outcome = search(params).unwrap();
+        assert_eq!(outcome.schedules.len(), 4);
+        assert_eq!(outcome.progress.combinations_total, 2);
+
+        let mut params = fixture_params();
+        params.seeds = vec![seed.clone()];
+        params.bound = 3.into();
+        assert_eq!(
+            engine_main(params).unwrap_err(),
+            ScheduleError::BoundTooLarge {
+                bound: 3,
+                available: 2
+            }
+        );
+
+        let mut params = fixture_params();
+        params.seeds = vec![
+            seed.clone(),
+            fixture_params().pool_list[1].grids()[0].clone(),
+        ];
+        params.bound = 1.into();
+        assert_eq!(
+            engine_main(params).unwrap_err(),
+            ScheduleError::SeedsExceedBound { seeds: 2, bound: 1 }
+        );
+
+        // A required pool is satisfied by a seed from it
+        let mut params = fixture_params();
+        params.seeds = vec![seed];
+        params.bound = 1.into();
+        params.pool_list[0].required = true;
+        assert_eq!(engine_main(params).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_availability() {
+        let evening = NaiveTime::parse_from_str("19:00", "%H:%M").unwrap();
+
+        // a1 and b2 go on past the evening
+        let mut params = fixture_params();
+        params.availability.not_after(evening);
+        let outcome = search(params).unwrap();
+        assert_eq!(outcome.unavailable_grids, vec![(0, 0), (1, 1)]);
+
+        let mut found: Vec<_> = outcome
+            .schedules
+            .iter()
+            .map(|schedule| {
+                let data: Vec<_> = schedule.grids().iter().map(|grid| *grid.data()).collect();
+                (data[0], data[1])
+            })
+            .collect();
+        found.sort();
+        assert_eq!(found, vec![(2, 1), (2, 3)]);
+
+        let mut params = fixture_params();
+        params.seeds = vec![params.pool_list[0].grids()[0].clone()];
+        params.availability.not_after(evening);
+        assert!(matches!(
+            engine_main(params).unwrap_err(),
+            ScheduleError::SeedUnavailable {
+                seed: 0,
+                day: Day::MONDAY,
+                ..
+            }
+        ));
+
+        // Unless seeds may be dropped, in which case a1 is left out like any clash
+        let mut params = fixture_params();
+        params.seeds = vec![
+            params.pool_list[0].grids()[0].clone(),
+            params.pool_list[1].grids()[0].clone(),
+        ];
+        params.availability.not_after(evening);
+        params.seed_policy = SeedPolicy::LargestCompatible;
+        let outcome = search(params).unwrap();
+        assert_eq!(outcome.dropped_seeds, vec![0]);
+        assert_eq!(outcome.schedules.len(), 1);
+        let data: Vec<_> = outcome.schedules[0]
+            .grids()
+            .iter()
+            .map(|grid| *grid.data())
+            .collect();
+        assert_eq!(data, vec![1, 2]);
+    }
+
+    #[test]
+    fn test_min_gap() {
+        let params = fixture_params();
+        let (pool_a, pool_b) = (&params.pool_list[0], &params.pool_list[1]);
+
+        // Every pair of groups that leaves five hours between classes
+        let mut expected = vec![];
+        for a in pool_a.grids() {
+            for b in pool_b.grids() {
+                let mut schedule = Schedule::with_gap(GapRule::new(300));
+                schedule.try_merge(a).unwrap();
+                if schedule.try_merge(b).is_ok() {
+                    expected.push((*a.data(), *b.data()));
+                }
+            }
+        }
+        assert!(!expected.is_empty() && expected.len() < 6);
+
+        let mut params = fixture_params();
+        params.gap = GapRule::new(300);
+        let mut found: Vec<_> = engine_main(params)
+            .unwrap()
+            .iter()
+            .map(|schedule| {
+                let grids = schedule.grids();
+                (*grids[0].data(), *grids[1].data())
+            })
+            .collect();
+        found.sort();
+        assert_eq!(found, expected);
+    }
+
+    #[test]
+    fn test_travel_times() {
+        // a1 takes place up north, everything else down south
+        let located = || {
+            let mut params = fixture_params();
+            for pool in params.pool_list.iter_mut() {
+                let pool_id = pool.pool_id;
+                for grid in pool.grid_list.iter_mut() {
+                    let north = pool_id == 1 && *grid.data() == 1;
+                    grid.location = Some(if north { "north" } else { "south" }.to_string());
+                }
+            }
+            params.travel_times.insert("north", "south", 300);
+            params
+        };
+
+        // There are only four hours between b1 and a1
+        let found = |params| {
+            let mut found: Vec<_> = engine_main(params)
+                .unwrap()
+                .iter()
+                .map(|schedule| {
+                    let grids = schedule.grids();
+                    (*grids[0].data(), *grids[1].data())
+                })
+                .collect();
+            found.sort();
+            found
+        };
+        assert_eq!(
+            found(located()),
+            vec![(1, 2), (1, 3), (2, 1), (2, 2), (2, 3)]
+        );
+
+        // Without locations, no travel is needed
+        let mut params = fixture_params();
+        params.travel_times.insert("north", "south", 300);
+        assert_eq!(found(params).len(), 6);
+
+        // Going from b3 to a1 takes ten hours on Mondays and Wednesdays
+        let mut params = located();
+        params.ranking = Some(Weighted::new(vec![(Criterion::TravelTime, 1.0)]));
+        let schedules = engine_main(params).unwrap();
+        let last = schedules.last().unwrap();
+        assert_eq!((*last.grids()[0].data(), *last.grids()[1].data()), (1, 3));
+        assert_eq!(Criterion::TravelTime.score(last), 600.0);
+        assert_eq!(Criterion::TravelTime.score(&schedules[0]), 0.0);
+
+        // A location that isn't listed is no time away from any other
+        let mut params = located();
+        params.pool_list[1].grid_list[0].location = Some("library".to_string());
+        assert_eq!(found(params).len(), 6);
+    }
+
+    #[test]
+    fn test_compatibility_matrix() {
+        let mut pool_1 = Pool::new(1);
+        pool_1.push(instance_grid_1().unwrap());
+        let mut pool_2 = Pool::new(2);
+        pool_2.push(instance_grid_2().unwrap());
+        let mut pool_3 = Pool::new(3);
+        pool_3.push(instance_grid_3().unwrap());
+        pool_3.push(instance_grid_4().unwrap());
+        pool_3.push(instance_grid_5().unwrap());
+
+        let pool_list = [pool_1, pool_2, pool_3];
+        let matrix = compatibility_matrix(&pool_list, &GapRule::default(), &TravelTimes::new());
+
+        assert_eq!(matrix.len(), 5);
+        assert!(matrix.compatible((0, 0), (1, 0)));
+        // Grid 3 clashes with grids 1 and 2 on Monday
+        assert!(!matrix.compatible((0, 0), (2, 0)));
+        assert!(!matrix.compatible((2, 0), (1, 0)));
+        assert_eq!(matrix.compatible_with((2, 1)), vec![(0, 0), (1, 0)]);
+        // Grids of the same pool are never taken together
+        assert!(!matrix.compatible((2, 0), (2, 1)));
+
+        let nested = matrix.to_nested();
+        assert_eq!(nested[0][0][2], vec![false, true, false]);
+        assert_eq!(nested[2][2][0], vec![false]);
+
+        // Grids 1 and 2 are back to back, which leaves no time for a break
+        let matrix = compatibility_matrix(&pool_list, &GapRule::new(15), &TravelTimes::new());
+        assert!(!matrix.compatible((0, 0), (1, 0)));
+
+        // Nor to get from one place to the other
+        let [mut pool_1, mut pool_2, pool_3] = pool_list;
+        pool_1.grid_list[0].location = Some("north".to_string());
+        pool_2.grid_list[0].location = Some("south".to_string());
+        let mut travel_times = TravelTimes::new();
+        travel_times.insert("north", "south", 30);
+        let matrix = compatibility_matrix(
+            &[pool_1, pool_2, pool_3],
+            &GapRule::default(),
+            &travel_times,
+        );
+        assert!(!matrix.compatible((0, 0), (1, 0)));
+        assert!(matrix.compatible((0, 0), (2, 1)));
+    }
+
+    #[test]
+    fn test_forward_checking() {
+        // Same results as checking every pair of grids by hand
+        let params = fixture_params();
+        let mut expected = vec![];
+        for a in params.pool_list[0].grids() {
+            for b in params.pool_list[1].grids() {
+                let mut schedule = Schedule::new();
+                schedule.try_merge(a).unwrap();
+                if schedule.try_merge(b).is_ok() {
+                    expected.push((*a.data(), *b.data()));
+                }
+            }
+        }
+
+        let mut found: Vec<_> = engine_main(fixture_params())
+            .unwrap()
+            .iter()
+            .map(|schedule| {
+                let mut grids = schedule.grids().to_vec();
+                grids.sort_by_key(|grid| grid.pool_id);
+                (*grids[0].data(), *grids[1].data())
+            })
+            .collect();
+        found.sort();
+        expected.sort();
+        assert_eq!(found, expected);
+
+        // Pools 1 and 2 have a group at each hour from 08:00 to 12:00 on Monday,
+        // while pool 3 leaves only 11:00 - 12:00 free.
+        let hours = [
+            ("08:00", "09:00"),
+            ("09:00", "10:00"),
+            ("10:00", "11:00"),
+            ("11:00", "12:00"),
+        ];
+
+        let mut pool_list = vec![Pool::new(1), Pool::new(2), Pool::new(3)];
+        for &(begin, end) in hours.iter() {
+            pool_list[0].push(Grid::from_vec(1, monday(begin, end), "%H:%M", 0).unwrap());
+            pool_list[1].push(Grid::from_vec(2, monday(begin, end), "%H:%M", 0).unwrap());
+        }
+        pool_list[2].push(Grid::from_vec(3, monday("08:00", "11:00"), "%H:%M", 0).unwrap());
+
+        let mut iter = solve_iter(EngineParams::new(pool_list, 3)).unwrap();
+
+        assert!(iter.next().is_none());
+        // Pool 3 goes first, then a single group of pool 1 leaves nothing for pool 2
+        assert_eq!(iter.nodes_visited(), 2);
+    }
 }
@@ -0,0 +1,172 @@
+/**
+ * src/error.rs
+ * Copyright (c) 2021 Carlos Carral <carloscarral13@gmail.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+use crate::grid::{Day, TimeRange};
+use chrono::NaiveTime;
+use serde::{Serialize, Serializer};
+use std::fmt::Display;
+use thiserror::Error;
+
+/// Everything that can go wrong while building grids or running the engine.
+/// `Id` is the type of the pool ids involved in the failure.
+///
+/// Serializes as `{ "kind": <variant>, "details": <fields> }`, so that callers
+/// can tell failures apart without parsing the message.
+#[derive(Clone, Debug, Error, PartialEq, Serialize)]
+#[serde(tag = "kind", content = "details")]
+pub enum ScheduleError<Id> {
+    #[error("Invalid time range on {day:?}: {start} - {end}")]
+    InvalidTimeRange {
+        day: Day,
+        start: NaiveTime,
+        end: NaiveTime,
+    },
+
+    #[error("Overlapping time ranges on {day:?}: {} - {} and {} - {}", .first.0, .first.1, .second.0, .second.1)]
+    OverlappingTimeRanges {
+        day: Day,
+        first: TimeRange,
+        second: TimeRange,
+    },
+
+    /// A grid of `incoming_pool` can't be merged because it clashes with
+    /// a grid of `existing_pool` that is already in the schedule.
+    #[error("Conflicting schedules: pool {incoming_pool} clashes with pool {existing_pool} on {day:?} at {} - {}", .overlap.0, .overlap.1)]
+    Conflict {
+        existing_pool: Id,
+        incoming_pool: Id,
+        day: Day,
+        overlap: TimeRange,
+    },
+
+    /// A grid of `incoming_pool` doesn't overlap with a grid of `existing_pool`,
+    /// but `interval` leaves less than the required break between both.
+    #[error("Not enough time between pool {existing_pool} and pool {incoming_pool} on {day:?} at {} - {}, {required_minutes} minutes are required", .interval.0, .interval.1)]
+    InsufficientGap {
+        existing_pool: Id,
+        incoming_pool: Id,
+        day: Day,
+        interval: TimeRange,
+        required_minutes: u32,
+    },
+
+    #[error("Found repeated pool id {0}. Pool id must be unique for any given pool")]
+    DuplicatePoolId(Id),
+
+    #[error("Invalid bound: minimum ({min}) is larger than maximum ({max})")]
+    InvalidBound { min: usize, max: usize },
+
+    /// `bound` is the smallest schedule size asked for.
+    #[error(
+        "Bound ({bound}) can't be larger than length of pool list + length of seeds ({available})"
+    )]
+    BoundTooLarge { bound: usize, available: usize },
+
+    #[error("Seeds ({seeds}) can't be more than the bound ({bound})")]
+    SeedsExceedBound { seeds: usize, bound: usize },
+
+    /// `required` counts the seeds too, as they are part of every schedule.
+    #[error("Required pools ({required}) can't be more than the bound ({bound})")]
+    RequiredExceedsBound { required: usize, bound: usize },
+
+    /// `index` is the position in the 14 column slice that is missing its
+    /// begin or end counterpart.
+    #[error("Unable to parse slice: column {index} has no matching begin/end time")]
+    MalformedSlice { index: usize },
+
+    #[error("Unable to parse time {value:?} at column {index}: {source}")]
+    InvalidTime {
+        index: usize,
+        value: String,
+        #[serde(serialize_with = "display")]
+        source: chrono::ParseError,
+    },
+
+    /// Seed number `seed` can't be merged with the seeds that come before it.
+    #[error("Seed {seed} clashes with pool {existing_pool} on {day:?} at {} - {}", .overlap.0, .overlap.1)]
+    SeedConflict {
+        seed: usize,
+        existing_pool: Id,
+        day: Day,
+        overlap: TimeRange,
+    },
+
+    /// Seed number `seed` doesn't overlap with the seeds that come before it,
+    /// but leaves less than the required break after or before one of them.
+    #[error("Seed {seed} is too close to pool {existing_pool} on {day:?} at {} - {}, {required_minutes} minutes are required", .interval.0, .interval.1)]
+    SeedTooClose {
+        seed: usize,
+        existing_pool: Id,
+        day: Day,
+        interval: TimeRange,
+        required_minutes: u32,
+    },
+
+    /// Seed number `seed` falls outside `EngineParams::availability`.
+    #[error("Seed {seed} is not within the availability on {day:?} at {} - {}", .time_range.0, .time_range.1)]
+    SeedUnavailable {
+        seed: usize,
+        day: Day,
+        time_range: TimeRange,
+    },
+}
+
+fn display<T: Display, S: Serializer>(value: &T, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(value)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::grid::Day;
+    use crate::utils::time;
+    use serde_json::json;
+
+    #[test]
+    fn test_serialize_error() {
+        let err = ScheduleError::SeedConflict {
+            seed: 1,
+            existing_pool: json!("physics"),
+            day: Day::MONDAY,
+            overlap: (time("08:00"), time("09:00")),
+        };
+
+        assert_eq!(
+            serde_json::to_value(&err).unwrap(),
+            json!({
+                "kind": "SeedConflict",
+                "details": {
+                    "seed": 1,
+                    "existing_pool": "physics",
+                    "day": "MONDAY",
+                    "overlap": ["08:00:00", "09:00:00"],
+                },
+            })
+        );
+        assert_eq!(
+            err.to_string(),
+            "Seed 1 clashes with pool \"physics\" on MONDAY at 08:00:00 - 09:00:00"
+        );
+
+        let err = ScheduleError::DuplicatePoolId(3);
+        assert_eq!(
+            serde_json::to_value(&err).unwrap(),
+            json!({ "kind": "DuplicatePoolId", "details": 3 })
+        );
+        assert!(err.to_string().starts_with("Found repeated pool id 3."));
+    }
+}
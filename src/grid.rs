@@ -1,5 +1,3 @@
-use std::fmt::Display;
-
 /**
  * src/grid.rs
  * Copyright (c) 2021 Carlos Carral <carloscarral13@gmail.com>
@@ -17,57 +15,139 @@ use std::fmt::Display;
  * You should have received a copy of the GNU General Public License
  * along with this program.  If not, see <http://www.gnu.org/licenses/>.
  */
-use anyhow::{bail, Result};
-use chrono::NaiveTime;
-use serde::{Deserialize, Serialize};
+use crate::error::ScheduleError;
+use crate::mask::WeekMask;
+use crate::travel::TravelTimes;
+use chrono::{Duration, NaiveTime};
+use serde::ser::SerializeStruct;
+use serde::{Deserialize, Serialize, Serializer};
+use std::fmt;
+use std::sync::Arc;
 use strum::IntoEnumIterator;
 use strum_macros::EnumIter;
 
-type TimeRange = (NaiveTime, NaiveTime);
+pub type TimeRange = (NaiveTime, NaiveTime);
 pub type GridList<K, V> = Vec<Grid<K, V>>;
 
+/// Separates several time ranges on the same day for `Grid::from_vec`
+/// i.e: begin "08:00;16:00" and end "10:00;17:00"
+pub const RANGE_SEPARATOR: char = ';';
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(try_from = "GridRepr<Id, D>")]
 pub struct Grid<Id: Eq, D> {
-    pub time_values: [Option<TimeRange>; 7],
+    /// Time ranges for each day of the week, sorted and non-overlapping.
+    /// The grid's mask is computed from these on construction, so they
+    /// shouldn't be modified afterwards.
+    pub time_values: [Vec<TimeRange>; 7],
     /// Used for checking if a schedule belongs to a pool
     pub pool_id: Id,
     /// Contains other info about a particular schedule instance
     /// i.e: { "teacher": "Stephen Hawking", "subject": "General Relativity 101"}
     data: D,
+    /// Where classes take place, as a key into `TravelTimes`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub location: Option<String>,
+    /// Precomputed occupancy of `time_values`, for fast conflict checks.
+    #[serde(skip)]
+    mask: WeekMask,
+}
+
+/// Serialized form of a grid, which lacks the mask and may hold invalid ranges.
+/// Days without classes may be `null` as well as empty, as they were when a
+/// day held a single optional range.
+#[derive(Deserialize)]
+struct GridRepr<Id, D> {
+    time_values: [Option<Vec<TimeRange>>; 7],
+    pool_id: Id,
+    data: D,
+    #[serde(default)]
+    location: Option<String>,
+}
+
+impl<Id: Eq, D> TryFrom<GridRepr<Id, D>> for Grid<Id, D> {
+    // Invalid ranges never involve a pool id, so any printable type will do
+    type Error = ScheduleError<String>;
+
+    fn try_from(repr: GridRepr<Id, D>) -> Result<Self, Self::Error> {
+        let mut time_values = repr.time_values.map(Option::unwrap_or_default);
+        sort_time_values(&mut time_values)?;
+        Ok(Grid {
+            mask: WeekMask::new(&time_values),
+            time_values,
+            pool_id: repr.pool_id,
+            data: repr.data,
+            location: repr.location,
+        })
+    }
+}
+
+/// Sorts the time ranges of each day, checking that none of them is inverted
+/// and that they don't overlap with each other.
+fn sort_time_values<E>(time_values: &mut [Vec<TimeRange>; 7]) -> Result<(), ScheduleError<E>> {
+    for (day, day_values) in Day::iter().zip(time_values.iter_mut()) {
+        for &(start, end) in day_values.iter() {
+            if start.gt(&end) {
+                return Err(ScheduleError::InvalidTimeRange { day, start, end });
+            }
+        }
+
+        // Ranges on the same day must not overlap with each other
+        day_values.sort();
+        for pair in day_values.windows(2) {
+            let (first, second) = (pair[0], pair[1]);
+            if first.1.gt(&second.0) {
+                return Err(ScheduleError::OverlappingTimeRanges { day, first, second });
+            }
+        }
+    }
+    Ok(())
 }
 
 impl<Id, D> Grid<Id, D>
 where
     Id: Eq,
 {
-    pub fn new(pool_id: Id, time_values: [Option<TimeRange>; 7], data: D) -> Result<Self> {
-        // Validate time ranges
-        for time_val in time_values.iter() {
-            if let &Some((v1, v2)) = time_val {
-                if v1.gt(&v2) {
-                    bail!("Invalid time range: {:?} - {:?}", v1, v2);
-                }
-            }
-        }
+    pub fn new(
+        pool_id: Id,
+        mut time_values: [Vec<TimeRange>; 7],
+        data: D,
+    ) -> Result<Self, ScheduleError<Id>> {
+        sort_time_values(&mut time_values)?;
         Ok(Grid {
             pool_id,
             data,
+            location: None,
+            mask: WeekMask::new(&time_values),
             time_values,
         })
     }
 
-    pub fn from_vec(id: Id, time_values_vec: [String; 14], fmt: &str, data: D) -> Result<Self> {
+    pub fn from_vec(
+        id: Id,
+        time_values_vec: [String; 14],
+        fmt: &str,
+        data: D,
+    ) -> Result<Self, ScheduleError<Id>> {
         // This function assumes time_values.len() == 14
         // and that begin-end times are ordered as such:
         // | MON | TUE | WED | THU | FRI | SAT | SUN |
         // |0 | 1|2 | 3|4 | 5|6 | 7|8 | 9|10|11|12|13|
         // where two empty strings mean no time range on that day.
+        // Several ranges on the same day are listed in both begin and end,
+        // split by RANGE_SEPARATOR.
 
-        let mut time_values: [Option<TimeRange>; 7] = [None; 7];
+        let mut time_values: [Vec<TimeRange>; 7] = Default::default();
 
-        if time_values_vec.len() != 14 {
-            bail!("Invalid slice length.");
-        }
+        let parse = |index: usize, value: &str| {
+            NaiveTime::parse_from_str(value.trim(), fmt).map_err(|source| {
+                ScheduleError::InvalidTime {
+                    index,
+                    value: value.to_string(),
+                    source,
+                }
+            })
+        };
 
         let mut j = 0;
         for i in (0..14).step_by(2) {
@@ -79,14 +159,23 @@ where
                     continue;
                 } else {
                     // Slice is malformed
-                    dbg!(time_values_vec);
-                    bail!("Unable to parse slice");
+                    let index = if begin.is_empty() { i } else { i + 1 };
+                    return Err(ScheduleError::MalformedSlice { index });
                 }
             } else {
-                time_values[j] = Some((
-                    NaiveTime::parse_from_str(begin, fmt)?,
-                    NaiveTime::parse_from_str(end, fmt)?,
-                ));
+                let begins = begin.split(RANGE_SEPARATOR);
+                let ends = end.split(RANGE_SEPARATOR);
+
+                let (begin_count, end_count) = (begins.clone().count(), ends.clone().count());
+                if begin_count != end_count {
+                    // Every begin time needs a matching end time
+                    let index = if begin_count < end_count { i } else { i + 1 };
+                    return Err(ScheduleError::MalformedSlice { index });
+                }
+
+                for (begin, end) in begins.zip(ends) {
+                    time_values[j].push((parse(i, begin)?, parse(i + 1, end)?));
+                }
                 j += 1;
             }
         }
@@ -95,10 +184,25 @@ where
     }
 
     pub fn free_at(&self, day: &Day, time_range: &TimeRange) -> bool {
+        self.overlap_at(day, time_range).is_none()
+    }
+
+    /// Returns the first interval where `time_range` clashes with this grid on `day`,
+    /// if there is one.
+    pub fn overlap_at(&self, day: &Day, time_range: &TimeRange) -> Option<TimeRange> {
+        self.overlaps_at(day, time_range).next()
+    }
+
+    /// Returns every interval where `time_range` clashes with this grid on `day`.
+    pub fn overlaps_at<'a>(
+        &'a self,
+        day: &Day,
+        time_range: &TimeRange,
+    ) -> impl Iterator<Item = TimeRange> + 'a {
         /* When checking if a schedule is compatible on a given day,
          * assuming that the time range is well formed,
          *  (start < end),
-         * we must ensure the following:
+         * we must ensure the following for every range on that day:
          *
          * with (my_start, my_end) and (other_start,other_end)
          *
@@ -114,21 +218,85 @@ where
          *
          * i.e: (07:00, 08:00) is compatible with (06:00,07:00)
          *
+         * Otherwise, both ranges overlap from the latest start to the
+         * earliest end.
          *
          * */
 
-        match self.time_values[*day as usize] {
-            Some((my_start, my_end)) => {
-                let (start, end) = time_range;
-                my_end.le(start) || my_start.ge(end)
-            }
-            None => true,
-        }
+        let &(start, end) = time_range;
+        self.time_values[*day as usize]
+            .iter()
+            .filter(move |(my_start, my_end)| !(my_end.le(&start) || my_start.ge(&end)))
+            .map(move |&(my_start, my_end)| (my_start.max(start), my_end.min(end)))
+    }
+
+    /// Returns every day and interval where `other` clashes with this grid.
+    pub fn overlaps_with<'a>(
+        &'a self,
+        other: &'a Grid<Id, D>,
+    ) -> impl Iterator<Item = (Day, TimeRange)> + 'a {
+        // Grids with disjoint masks can't clash, skip the exact comparison
+        let candidates = if self.mask.intersects(&other.mask) {
+            Day::iter().collect()
+        } else {
+            vec![]
+        };
+
+        candidates.into_iter().flat_map(move |day| {
+            other.time_values[day as usize]
+                .iter()
+                .flat_map(move |time_values| self.overlaps_at(&day, time_values))
+                .map(move |overlap| (day, overlap))
+        })
+    }
+
+    pub fn compatible_with(&self, other: &Grid<Id, D>) -> bool {
+        self.overlaps_with(other).next().is_none()
+    }
+
+    /// Returns every day and interval where `other` is less than `gap` minutes
+    /// away from this grid, overlaps included. The interval is either the
+    /// overlap or the break between both classes.
+    pub fn too_close_to<'a>(
+        &'a self,
+        other: &'a Grid<Id, D>,
+        gap: u32,
+    ) -> impl Iterator<Item = (Day, TimeRange)> + 'a {
+        let gap = Duration::minutes(gap as i64);
+
+        Day::iter().flat_map(move |day| {
+            let mine = &self.time_values[day as usize];
+            let theirs = &other.time_values[day as usize];
+
+            mine.iter()
+                .flat_map(move |&a| theirs.iter().map(move |&b| (a, b)))
+                .filter(move |&(a, b)| (b.0 - a.1).max(a.0 - b.1) < gap)
+                .map(move |(a, b)| {
+                    let interval = if a.0 < b.1 && b.0 < a.1 {
+                        (a.0.max(b.0), a.1.min(b.1))
+                    } else if a.1 <= b.0 {
+                        (a.1, b.0)
+                    } else {
+                        (b.1, a.0)
+                    };
+                    (day, interval)
+                })
+        })
+    }
+
+    /// Sets where the grid's classes take place.
+    pub fn with_location(mut self, location: impl Into<String>) -> Self {
+        self.location = Some(location.into());
+        self
     }
 
     pub fn data(&self) -> &D {
         &self.data
     }
+
+    pub fn mask(&self) -> &WeekMask {
+        &self.mask
+    }
 }
 
 /// A pool is a data structure that stores Grids that share the same Id.
@@ -142,6 +310,9 @@ where
 pub struct Pool<Id: Eq, D> {
     pub grid_list: Vec<Grid<Id, D>>,
     pub pool_id: Id,
+    /// Required pools are part of every schedule the engine generates.
+    #[serde(default)]
+    pub required: bool,
 }
 
 impl<Id, D> Pool<Id, D>
@@ -152,6 +323,7 @@ where
         Pool {
             grid_list: vec![],
             pool_id,
+            required: false,
         }
     }
 
@@ -164,7 +336,7 @@ where
     }
 }
 
-#[derive(Copy, Clone, EnumIter)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, EnumIter, Serialize, Deserialize)]
 pub enum Day {
     MONDAY = 0,
     TUESDAY = 1,
@@ -175,9 +347,150 @@ pub enum Day {
     SUNDAY = 6,
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+/// Minutes required between two grids.
+pub type GapFn<Id, D> = Arc<dyn Fn(&Grid<Id, D>, &Grid<Id, D>) -> u32 + Send + Sync>;
+
+/// Minimum break between two classes on the same day, e.g. so that there is
+/// time to get from one campus to the other.
+#[derive(Deserialize)]
+pub struct GapRule<Id: Eq, D> {
+    /// Minutes required between any two classes.
+    #[serde(default)]
+    pub min_gap_minutes: u32,
+    /// Minutes required between two specific classes, e.g. depending on where
+    /// each of them takes place. The larger of this and `min_gap_minutes` applies.
+    #[serde(skip)]
+    pub between: Option<GapFn<Id, D>>,
+    /// Time it takes to get between the locations of two classes.
+    #[serde(skip)]
+    pub travel_times: Option<Arc<TravelTimes>>,
+}
+
+impl<Id: Eq, D> GapRule<Id, D> {
+    pub fn new(min_gap_minutes: u32) -> Self {
+        GapRule {
+            min_gap_minutes,
+            between: None,
+            travel_times: None,
+        }
+    }
+
+    /// Also requires the time it takes to go between the locations of two
+    /// classes by the fastest route, unless `travel_times` is empty.
+    pub fn with_travel_times(mut self, travel_times: TravelTimes) -> Self {
+        if !travel_times.is_empty() {
+            self.travel_times = Some(Arc::new(travel_times.fastest_routes()));
+        }
+        self
+    }
+
+    /// Minutes required between `a` and `b`.
+    pub fn required(&self, a: &Grid<Id, D>, b: &Grid<Id, D>) -> u32 {
+        let between = self.between.as_ref().map_or(0, |between| between(a, b));
+        let travel = self
+            .travel_times
+            .as_ref()
+            .map_or(0, |travel_times| travel_times.between(a, b));
+        self.min_gap_minutes.max(between).max(travel)
+    }
+
+    /// Whether no break is ever required, so that only overlaps matter.
+    pub fn is_zero(&self) -> bool {
+        self.min_gap_minutes == 0 && self.between.is_none() && self.travel_times.is_none()
+    }
+
+    /// Whether `a` and `b` neither overlap nor are too close to each other.
+    pub fn allows(&self, a: &Grid<Id, D>, b: &Grid<Id, D>) -> bool {
+        match self.required(a, b) {
+            0 => a.compatible_with(b),
+            gap => a.too_close_to(b, gap).next().is_none(),
+        }
+    }
+}
+
+impl<Id: Eq, D> Default for GapRule<Id, D> {
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
+
+impl<Id: Eq, D> Clone for GapRule<Id, D> {
+    fn clone(&self) -> Self {
+        GapRule {
+            min_gap_minutes: self.min_gap_minutes,
+            between: self.between.clone(),
+            travel_times: self.travel_times.clone(),
+        }
+    }
+}
+
+impl<Id: Eq, D> fmt::Debug for GapRule<Id, D> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("GapRule")
+            .field("min_gap_minutes", &self.min_gap_minutes)
+            .field("between", &self.between.as_ref().map(|_| ".."))
+            .field("travel_times", &self.travel_times)
+            .finish()
+    }
+}
+
+/// Describes a clash between a grid that is already part of a schedule
+/// and a grid that is being checked against it.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Conflict<Id> {
+    /// Position of the clashing grid in the schedule.
+    pub index: usize,
+    /// Pool the clashing grid belongs to.
+    pub pool_id: Id,
+    pub day: Day,
+    /// Interval in which both grids overlap.
+    pub overlap: TimeRange,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(from = "ScheduleRepr<Id, D>")]
 pub struct Schedule<Id: Eq + Clone + ToOwned, D: Clone + ToOwned> {
     grids: Vec<Grid<Id, D>>,
+    /// `masks[i]` is the union of the masks of `grids[..=i]`, so that the
+    /// mask of the whole schedule survives `remove_last_added`.
+    masks: Vec<WeekMask>,
+    /// Break required between classes, on top of them not overlapping.
+    gap: GapRule<Id, D>,
+}
+
+/// Serialized form of a schedule, which lacks the masks.
+#[derive(Deserialize)]
+struct ScheduleRepr<Id: Eq, D> {
+    grids: Vec<Grid<Id, D>>,
+}
+
+impl<Id, D> From<ScheduleRepr<Id, D>> for Schedule<Id, D>
+where
+    Id: Eq + Clone,
+    D: Clone,
+{
+    fn from(repr: ScheduleRepr<Id, D>) -> Self {
+        let mut schedule = Schedule::new();
+        for grid in repr.grids {
+            schedule.push(grid);
+        }
+        schedule
+    }
+}
+
+// Serialized schedules are tagged with their pool count, so that results of
+// a ranged bound can be told apart without counting grids.
+impl<Id, D> Serialize for Schedule<Id, D>
+where
+    Id: Eq + Clone + Serialize,
+    D: Clone + Serialize,
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("Schedule", 2)?;
+        state.serialize_field("grids", &self.grids)?;
+        state.serialize_field("pool_count", &self.pool_count())?;
+        state.end()
+    }
 }
 
 impl<Id, D> Schedule<Id, D>
@@ -186,39 +499,149 @@ where
     D: Clone + ToOwned,
 {
     pub fn new() -> Self {
-        Schedule::<Id, D> { grids: vec![] }
+        Self::with_gap(GapRule::default())
     }
 
-    pub fn try_merge(&mut self, grid: &Grid<Id, D>) -> Result<()> {
-        for day in Day::iter() {
-            for grid_inner in &self.grids {
-                if let Some(time_values) = grid_inner.time_values[day as usize] {
-                    if !grid.free_at(&day, &time_values) {
-                        bail!("Error: Conflicting schedules");
-                    }
-                }
+    /// Empty schedule whose grids must be at least `gap` apart.
+    pub fn with_gap(gap: GapRule<Id, D>) -> Self {
+        Schedule::<Id, D> {
+            grids: vec![],
+            masks: vec![],
+            gap,
+        }
+    }
+
+    pub fn gap(&self) -> &GapRule<Id, D> {
+        &self.gap
+    }
+
+    pub fn try_merge(&mut self, grid: &Grid<Id, D>) -> Result<(), ScheduleError<Id>> {
+        // Only look for the exact clash when the masks say there may be one
+        if self.mask().intersects(grid.mask()) {
+            if let Some(conflict) = self.conflicts(grid).next() {
+                return Err(ScheduleError::Conflict {
+                    existing_pool: conflict.pool_id,
+                    incoming_pool: grid.pool_id.clone(),
+                    day: conflict.day,
+                    overlap: conflict.overlap,
+                });
             }
         }
 
-        self.grids.push((*grid).clone());
+        if let Some((existing, day, interval, required_minutes)) = self.gap_violation(grid) {
+            return Err(ScheduleError::InsufficientGap {
+                existing_pool: existing.pool_id.clone(),
+                incoming_pool: grid.pool_id.clone(),
+                day,
+                interval,
+                required_minutes,
+            });
+        }
+
+        self.push((*grid).clone());
 
         Ok(())
     }
 
+    /// Whether `grid` can be merged into the schedule.
+    pub fn accepts(&self, grid: &Grid<Id, D>) -> bool {
+        (!self.mask().intersects(grid.mask()) || self.conflicts(grid).next().is_none())
+            && self.gap_violation(grid).is_none()
+    }
+
+    /// First grid of the schedule that is too close to `grid`, along with the
+    /// day, the break between both and the minutes required.
+    fn gap_violation(&self, grid: &Grid<Id, D>) -> Option<(&Grid<Id, D>, Day, TimeRange, u32)> {
+        if self.gap.is_zero() {
+            return None;
+        }
+
+        self.grids.iter().find_map(|existing| {
+            let required = self.gap.required(existing, grid);
+            existing
+                .too_close_to(grid, required)
+                .next()
+                .map(|(day, interval)| (existing, day, interval, required))
+        })
+    }
+
+    /// Adds `grid` without checking for conflicts.
+    pub(crate) fn push(&mut self, grid: Grid<Id, D>) {
+        self.masks.push(self.mask().union(grid.mask()));
+        self.grids.push(grid);
+    }
+
+    /// Union of the masks of every grid in the schedule.
+    pub fn mask(&self) -> WeekMask {
+        self.masks.last().copied().unwrap_or_default()
+    }
+
+    /// Lists every grid in the schedule that clashes with `grid`, with the
+    /// day and exact interval of each clash.
+    pub fn conflicts_with(&self, grid: &Grid<Id, D>) -> Vec<Conflict<Id>> {
+        self.conflicts(grid).collect()
+    }
+
+    fn conflicts<'a>(&'a self, grid: &'a Grid<Id, D>) -> impl Iterator<Item = Conflict<Id>> + 'a {
+        self.grids
+            .iter()
+            .enumerate()
+            .flat_map(move |(index, grid_inner)| {
+                grid.overlaps_with(grid_inner)
+                    .map(move |(day, overlap)| Conflict {
+                        index,
+                        pool_id: grid_inner.pool_id.clone(),
+                        day,
+                        overlap,
+                    })
+            })
+    }
+
+    pub fn grids(&self) -> &[Grid<Id, D>] {
+        &self.grids
+    }
+
     pub fn remove_last_added(&mut self) -> Option<Grid<Id, D>> {
+        self.masks.pop();
         self.grids.pop()
     }
 
     pub fn len(&self) -> usize {
         self.grids.len()
     }
+
+    /// Number of pools, seeds included, the schedule picks a grid from.
+    pub fn pool_count(&self) -> usize {
+        self.grids.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.grids.is_empty()
+    }
+
+    /// Whether some grid of the schedule belongs to pool `pool_id`.
+    pub fn has_pool(&self, pool_id: &Id) -> bool {
+        self.grids.iter().any(|grid| grid.pool_id == *pool_id)
+    }
+}
+
+impl<Id, D> Default for Schedule<Id, D>
+where
+    Id: Eq + PartialEq + Clone + ToOwned,
+    D: Clone + ToOwned,
+{
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 #[cfg(test)]
 mod test {
 
-    use crate::grid::Schedule;
+    use crate::error::ScheduleError;
+    use crate::grid::{Conflict, Day, GapRule, Grid, Schedule};
     use crate::utils::*;
+    use std::sync::Arc;
 
     #[test]
     fn test_try_merge() {
@@ -226,7 +649,249 @@ mod test {
         let grid_5 = instance_grid_5().unwrap();
 
         let mut schedule_2 = Schedule::new();
-        schedule_2.try_merge(&grid_5);
-        assert!(schedule_2.try_merge(&grid_4).is_err());
+        schedule_2.try_merge(&grid_5).unwrap();
+        assert_eq!(
+            schedule_2.try_merge(&grid_4),
+            Err(ScheduleError::Conflict {
+                existing_pool: 3,
+                incoming_pool: 3,
+                day: Day::TUESDAY,
+                overlap: (time("18:00"), time("18:30")),
+            })
+        );
+    }
+
+    #[test]
+    fn test_multiple_ranges_per_day() {
+        let lecture_and_lab =
+            Grid::from_vec(1, monday("16:00;08:00", "17:00;10:00"), "%H:%M", 0).unwrap();
+
+        // Ranges are kept sorted
+        assert_eq!(
+            lecture_and_lab.time_values[0],
+            vec![
+                (time("08:00"), time("10:00")),
+                (time("16:00"), time("17:00")),
+            ]
+        );
+        assert!(!lecture_and_lab.free_at(&Day::MONDAY, &(time("16:30"), time("18:00"))));
+
+        let in_between = Grid::from_vec(2, monday("12:00", "13:00"), "%H:%M", 0).unwrap();
+        let overlaps_lab = Grid::from_vec(3, monday("16:30", "18:00"), "%H:%M", 0).unwrap();
+
+        let mut schedule = Schedule::new();
+        schedule.try_merge(&lecture_and_lab).unwrap();
+        assert!(schedule.try_merge(&in_between).is_ok());
+        assert!(schedule.try_merge(&overlaps_lab).is_err());
+    }
+
+    #[test]
+    fn test_overlapping_ranges_per_day() {
+        assert_eq!(
+            Grid::from_vec(1, monday("08:00;09:00", "10:00;11:00"), "%H:%M", 0).unwrap_err(),
+            ScheduleError::OverlappingTimeRanges {
+                day: Day::MONDAY,
+                first: (time("08:00"), time("10:00")),
+                second: (time("09:00"), time("11:00")),
+            }
+        );
+        // Begin and end times must come in pairs
+        assert_eq!(
+            Grid::from_vec(1, monday("08:00;16:00", "10:00"), "%H:%M", 0).unwrap_err(),
+            ScheduleError::MalformedSlice { index: 1 }
+        );
+    }
+
+    #[test]
+    fn test_from_vec_errors() {
+        assert_eq!(
+            Grid::from_vec(1, monday("08:00", ""), "%H:%M", 0).unwrap_err(),
+            ScheduleError::MalformedSlice { index: 1 }
+        );
+        assert!(matches!(
+            Grid::from_vec(1, monday("8 am", "10:00"), "%H:%M", 0),
+            Err(ScheduleError::InvalidTime { index: 0, .. })
+        ));
+        assert_eq!(
+            Grid::from_vec(1, monday("10:00", "08:00"), "%H:%M", 0).unwrap_err(),
+            ScheduleError::InvalidTimeRange {
+                day: Day::MONDAY,
+                start: time("10:00"),
+                end: time("08:00"),
+            }
+        );
+    }
+
+    #[test]
+    fn test_conflicts_with() {
+        let lecture_and_lab =
+            Grid::from_vec(1, monday("08:00;16:00", "10:00;17:00"), "%H:%M", 0).unwrap();
+
+        let mut tuesday: [String; 14] = Default::default();
+        tuesday[2] = "09:00".to_string();
+        tuesday[3] = "11:00".to_string();
+        let tuesday_only = Grid::from_vec(2, tuesday, "%H:%M", 0).unwrap();
+
+        let mut schedule = Schedule::new();
+        schedule.try_merge(&lecture_and_lab).unwrap();
+        schedule.try_merge(&tuesday_only).unwrap();
+
+        let mut incoming: [String; 14] = monday("09:00", "16:30");
+        incoming[2] = "10:00".to_string();
+        incoming[3] = "12:00".to_string();
+        let incoming = Grid::from_vec(3, incoming, "%H:%M", 0).unwrap();
+
+        assert_eq!(
+            schedule.conflicts_with(&incoming),
+            vec![
+                Conflict {
+                    index: 0,
+                    pool_id: 1,
+                    day: Day::MONDAY,
+                    overlap: (time("09:00"), time("10:00")),
+                },
+                Conflict {
+                    index: 0,
+                    pool_id: 1,
+                    day: Day::MONDAY,
+                    overlap: (time("16:00"), time("16:30")),
+                },
+                Conflict {
+                    index: 1,
+                    pool_id: 2,
+                    day: Day::TUESDAY,
+                    overlap: (time("10:00"), time("11:00")),
+                },
+            ]
+        );
+
+        let free = Grid::from_vec(4, monday("12:00", "13:00"), "%H:%M", 0).unwrap();
+        assert!(schedule.conflicts_with(&free).is_empty());
+    }
+
+    #[test]
+    fn test_unaligned_times() {
+        // Both grids share the 10:00 - 10:05 slot of their masks without clashing
+        let first = Grid::from_vec(1, monday("08:00", "10:03"), "%H:%M", 0).unwrap();
+        let second = Grid::from_vec(2, monday("10:03", "12:00"), "%H:%M", 0).unwrap();
+        let third = Grid::from_vec(3, monday("10:04", "10:05"), "%H:%M", 0).unwrap();
+
+        let mut schedule = Schedule::new();
+        schedule.try_merge(&first).unwrap();
+        assert!(schedule.try_merge(&second).is_ok());
+        assert!(schedule.try_merge(&third).is_err());
+
+        // The schedule's mask goes back to its previous state
+        schedule.remove_last_added();
+        assert!(schedule.try_merge(&third).is_ok());
+        assert!(schedule.try_merge(&second).is_err());
+    }
+
+    #[test]
+    fn test_min_gap() {
+        let first = Grid::from_vec(1, monday("08:00", "10:00"), "%H:%M", "north").unwrap();
+        let second = Grid::from_vec(2, monday("10:00", "12:00"), "%H:%M", "north").unwrap();
+        let third = Grid::from_vec(3, monday("10:15", "12:00"), "%H:%M", "south").unwrap();
+        let fourth = Grid::from_vec(4, monday("10:30", "12:00"), "%H:%M", "south").unwrap();
+
+        // Back to back classes are fine unless a break is required
+        let mut schedule = Schedule::new();
+        schedule.try_merge(&first).unwrap();
+        assert!(schedule.accepts(&second));
+
+        let mut schedule = Schedule::with_gap(GapRule::new(15));
+        schedule.try_merge(&first).unwrap();
+        assert!(!schedule.accepts(&second));
+        assert_eq!(
+            schedule.try_merge(&second).unwrap_err(),
+            ScheduleError::InsufficientGap {
+                existing_pool: 1,
+                incoming_pool: 2,
+                day: Day::MONDAY,
+                interval: (time("10:00"), time("10:00")),
+                required_minutes: 15,
+            }
+        );
+        assert!(schedule.accepts(&third));
+
+        // Changing campus takes longer
+        let mut gap = GapRule::new(15);
+        gap.between = Some(Arc::new(
+            |a: &Grid<u32, &str>, b: &Grid<u32, &str>| {
+                if a.data() == b.data() {
+                    0
+                } else {
+                    30
+                }
+            },
+        ));
+        let mut schedule = Schedule::with_gap(gap);
+        schedule.try_merge(&first).unwrap();
+        assert!(matches!(
+            schedule.try_merge(&third).unwrap_err(),
+            ScheduleError::InsufficientGap {
+                interval,
+                required_minutes: 30,
+                ..
+            } if interval == (time("10:00"), time("10:15"))
+        ));
+        assert!(schedule.try_merge(&fourth).is_ok());
+
+        // Overlaps are still reported as such
+        assert_eq!(
+            first
+                .too_close_to(
+                    &Grid::from_vec(5, monday("09:00", "11:00"), "%H:%M", "").unwrap(),
+                    0
+                )
+                .collect::<Vec<_>>(),
+            vec![(Day::MONDAY, (time("09:00"), time("10:00")))]
+        );
+    }
+
+    #[test]
+    fn test_deserialized_masks() {
+        let grid = Grid::from_vec(1, monday("08:00", "10:00"), "%H:%M", 0).unwrap();
+        let deserialized: Grid<u32, u32> =
+            serde_json::from_str(&serde_json::to_string(&grid).unwrap()).unwrap();
+        assert_eq!(deserialized.mask(), grid.mask());
+
+        let mut schedule = Schedule::new();
+        schedule.try_merge(&grid).unwrap();
+        let deserialized: Schedule<u32, u32> =
+            serde_json::from_str(&serde_json::to_string(&schedule).unwrap()).unwrap();
+        assert_eq!(deserialized.mask(), schedule.mask());
+    }
+
+    #[test]
+    fn test_deserialized_ranges() {
+        let grid = |monday: &str| {
+            let json = format!(
+                r#"{{"time_values":[{},[],[],[],[],[],[]],"pool_id":1,"data":0}}"#,
+                monday
+            );
+            serde_json::from_str::<Grid<u32, u32>>(&json)
+        };
+
+        let unsorted = grid(r#"[["10:00:00","11:00:00"],["08:00:00","09:00:00"]]"#).unwrap();
+        assert_eq!(
+            unsorted.time_values[0],
+            vec![
+                (time("08:00"), time("09:00")),
+                (time("10:00"), time("11:00"))
+            ]
+        );
+
+        let inverted = grid(r#"[["11:00:00","10:00:00"]]"#).unwrap_err();
+        assert!(inverted.to_string().contains("Invalid time range"));
+
+        let overlapping = grid(r#"[["08:00:00","10:00:00"],["09:00:00","11:00:00"]]"#).unwrap_err();
+        assert!(overlapping.to_string().contains("Overlapping time ranges"));
+
+        // Days without classes may be null
+        let json = r#"{"time_values":[[["08:00:00","09:00:00"]],null,[],null,null,null,null],"pool_id":1,"data":0}"#;
+        let grid = serde_json::from_str::<Grid<u32, u32>>(json).unwrap();
+        assert_eq!(grid.time_values[0], vec![(time("08:00"), time("09:00"))]);
+        assert!(grid.time_values[1..].iter().all(Vec::is_empty));
     }
 }
@@ -0,0 +1,918 @@
+/**
+ * src/ical.rs
+ * Copyright (c) 2021 Carlos Carral <carloscarral13@gmail.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+use crate::availability::Availability;
+use crate::grid::{Day, Grid, Schedule, TimeRange};
+use chrono::{
+    DateTime, Datelike, Duration, FixedOffset, NaiveDate, NaiveDateTime, NaiveTime, Offset,
+    TimeZone, Timelike, Utc,
+};
+use chrono_tz::{OffsetComponents, OffsetName, Tz};
+use serde::Deserialize;
+use strum::IntoEnumIterator;
+use thiserror::Error;
+
+/// Longest line allowed by RFC 5545, in octets, not counting the line break.
+const MAX_LINE_LENGTH: usize = 75;
+
+/// Text of the event generated for a grid.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+pub struct EventText {
+    pub summary: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub location: Option<String>,
+}
+
+impl<Id, D> Schedule<Id, D>
+where
+    Id: Eq + Clone,
+    D: Clone,
+{
+    /// Writes the schedule as an iCalendar file, with one event repeating
+    /// weekly from `term_start` to `term_end`, both inclusive, for every time
+    /// range of every grid.
+    ///
+    /// Times are local to `tz`, whose offsets during the term are written
+    /// out as a VTIMEZONE, so that classes keep their time of day across
+    /// daylight saving changes. No occurrence is generated on the dates listed
+    /// in `holidays`. `describe` fills in the text of the events of each grid.
+    ///
+    /// Event UIDs start with `uid_prefix`, which should be different for every
+    /// schedule so that importing one doesn't replace the events of another.
+    /// `stamp` is the time the file is written at.
+    #[allow(clippy::too_many_arguments)]
+    pub fn to_ical<F>(
+        &self,
+        term_start: NaiveDate,
+        term_end: NaiveDate,
+        tz: Tz,
+        holidays: &[NaiveDate],
+        uid_prefix: &str,
+        stamp: DateTime<Utc>,
+        describe: F,
+    ) -> String
+    where
+        F: Fn(&Grid<Id, D>) -> EventText,
+    {
+        let stamp = stamp.format("%Y%m%dT%H%M%SZ").to_string();
+        let mut lines = vec![
+            "BEGIN:VCALENDAR".to_string(),
+            "VERSION:2.0".to_string(),
+            "PRODID:-//schedule-engine//EN".to_string(),
+            "CALSCALE:GREGORIAN".to_string(),
+        ];
+        lines.extend(vtimezone(tz, term_start, term_end));
+
+        for (i, grid) in self.grids().iter().enumerate() {
+            let text = describe(grid);
+
+            for day in Day::iter() {
+                let first = first_weekday(term_start, day);
+                if first > term_end {
+                    continue;
+                }
+                let count = (term_end - first).num_days() / 7 + 1;
+
+                for (k, &(start, end)) in grid.time_values[day as usize].iter().enumerate() {
+                    lines.push("BEGIN:VEVENT".to_string());
+                    lines.push(format!(
+                        "UID:{}-{}-{}-{}-{}@schedule-engine",
+                        uid_prefix,
+                        date_value(term_start),
+                        i,
+                        day as usize,
+                        k
+                    ));
+                    lines.push(format!("DTSTAMP:{}", stamp));
+                    lines.push(format!("DTSTART;TZID={}:{}", tz, date_time(first, start)));
+                    lines.push(format!("DTEND;TZID={}:{}", tz, date_time(first, end)));
+                    lines.push(format!(
+                        "RRULE:FREQ=WEEKLY;BYDAY={};COUNT={}",
+                        weekday_code(day),
+                        count
+                    ));
+
+                    for &holiday in holidays {
+                        if holiday >= first
+                            && holiday <= term_end
+                            && holiday.weekday().num_days_from_monday() == day as u32
+                        {
+                            lines.push(format!("EXDATE;TZID={}:{}", tz, date_time(holiday, start)));
+                        }
+                    }
+
+                    lines.push(format!("SUMMARY:{}", escape(&text.summary)));
+                    if let Some(description) = &text.description {
+                        lines.push(format!("DESCRIPTION:{}", escape(description)));
+                    }
+                    if let Some(location) = &text.location {
+                        lines.push(format!("LOCATION:{}", escape(location)));
+                    }
+                    lines.push("END:VEVENT".to_string());
+                }
+            }
+        }
+
+        lines.push("END:VCALENDAR".to_string());
+
+        lines.iter().map(|line| fold(line)).collect()
+    }
+}
+
+/// One weekly occurrence of a recurring calendar event.
+#[derive(Clone, Debug, PartialEq)]
+pub struct WeeklyEvent {
+    pub summary: String,
+    pub day: Day,
+    pub time_range: TimeRange,
+}
+
+#[derive(Debug, Error, PartialEq)]
+pub enum IcalError {
+    /// `line` is the line number in the input where the event begins.
+    #[error("Line {line}: event has no {property}")]
+    MissingProperty { line: usize, property: &'static str },
+
+    /// `line` is the line number in the input where the property begins.
+    #[error("Line {line}: unable to parse {value:?}")]
+    InvalidValue { line: usize, value: String },
+
+    #[error("Line {line}: unexpected end of calendar")]
+    UnexpectedEnd { line: usize },
+
+    /// The property at `line` is in UTC, but no offset to convert it with was given.
+    #[error("Line {line}: UTC time given without an offset to convert it to")]
+    UtcTime { line: usize },
+}
+
+/// Property of a calendar component, after unfolding.
+struct Property<'a> {
+    line: usize,
+    name: String,
+    params: &'a str,
+    value: &'a str,
+}
+
+/// Reads every weekly recurring event of an iCalendar file, one entry per
+/// day it repeats on.
+///
+/// Times are taken as written, so that events in a time zone keep their local
+/// time of day. UTC times are converted to `utc_offset`, and rejected when it
+/// is `None`. Events that don't repeat weekly, repeat every few weeks, last
+/// the whole day or end on a later day are skipped, as a grid can't represent
+/// them.
+pub fn read_weekly_events(
+    input: &str,
+    utc_offset: Option<FixedOffset>,
+) -> Result<Vec<WeeklyEvent>, IcalError> {
+    let lines = unfold(input);
+    let mut events = vec![];
+    let mut current: Option<(usize, Vec<Property>)> = None;
+    // Number of components, such as alarms, open within the current event
+    let mut nested = 0;
+
+    for (line, content) in lines.iter() {
+        let property = parse_property(*line, content)?;
+
+        match (property.name.as_str(), property.value, current.as_mut()) {
+            ("BEGIN", "VEVENT", None) => current = Some((*line, vec![])),
+            ("BEGIN", _, Some(_)) => nested += 1,
+            ("END", _, Some(_)) if nested > 0 => nested -= 1,
+            ("END", "VEVENT", Some(_)) => {
+                if let Some((begin, properties)) = current.take() {
+                    events.extend(weekly_event(begin, &properties, utc_offset)?);
+                }
+            }
+            (_, _, Some((_, properties))) if nested == 0 => properties.push(property),
+            _ => {}
+        }
+    }
+
+    match current {
+        Some((begin, _)) => Err(IcalError::UnexpectedEnd { line: begin }),
+        None => Ok(events),
+    }
+}
+
+/// Availability that forbids classes whenever any of `events` takes place,
+/// merging the ones that overlap. Unlike a seed, it takes up no room in the
+/// schedule.
+pub fn busy_availability(events: &[WeeklyEvent]) -> Availability {
+    let mut windows: [Vec<TimeRange>; 7] = Default::default();
+
+    for event in events {
+        windows[event.day as usize].push(event.time_range);
+    }
+
+    let mut availability = Availability::new();
+    for (day, day_windows) in Day::iter().zip(windows.iter_mut()) {
+        day_windows.sort();
+        let mut merged: Vec<TimeRange> = Vec::with_capacity(day_windows.len());
+        for &(start, end) in day_windows.iter() {
+            match merged.last_mut() {
+                Some(last) if start <= last.1 => last.1 = last.1.max(end),
+                _ => merged.push((start, end)),
+            }
+        }
+        for window in merged {
+            availability.forbid(day, window);
+        }
+    }
+
+    availability
+}
+
+/// Joins folded lines back together, keeping the line number each one starts at.
+fn unfold(input: &str) -> Vec<(usize, String)> {
+    let mut lines: Vec<(usize, String)> = vec![];
+
+    for (i, line) in input.lines().enumerate() {
+        let line = line.trim_end_matches('\r');
+        match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(continuation), Some((_, last))) => last.push_str(continuation),
+            _ if line.is_empty() => {}
+            _ => lines.push((i + 1, line.to_string())),
+        }
+    }
+
+    lines
+}
+
+fn parse_property(line: usize, content: &str) -> Result<Property<'_>, IcalError> {
+    // The value starts after the first colon that is not within a quoted parameter
+    let mut quoted = false;
+    let colon = content.char_indices().find_map(|(i, c)| match c {
+        '"' => {
+            quoted = !quoted;
+            None
+        }
+        ':' if !quoted => Some(i),
+        _ => None,
+    });
+
+    let Some(colon) = colon else {
+        return Err(IcalError::InvalidValue {
+            line,
+            value: content.to_string(),
+        });
+    };
+
+    let (head, value) = (&content[..colon], &content[colon + 1..]);
+    let (name, params) = head.split_once(';').unwrap_or((head, ""));
+
+    Ok(Property {
+        line,
+        name: name.to_ascii_uppercase(),
+        params,
+        value,
+    })
+}
+
+/// Turns the properties of a VEVENT that begins at line `begin` into one
+/// `WeeklyEvent` per day it repeats on, or none if it can't be represented.
+fn weekly_event(
+    begin: usize,
+    properties: &[Property],
+    utc_offset: Option<FixedOffset>,
+) -> Result<Vec<WeeklyEvent>, IcalError> {
+    let find = |name: &str| properties.iter().find(|property| property.name == name);
+    let invalid = |property: &Property| IcalError::InvalidValue {
+        line: property.line,
+        value: property.value.to_string(),
+    };
+
+    let Some(rrule) = find("RRULE") else {
+        return Ok(vec![]);
+    };
+    let rule: Vec<(&str, &str)> = rrule
+        .value
+        .split(';')
+        .filter_map(|part| part.split_once('='))
+        .collect();
+    let rule_part = |key: &str| {
+        rule.iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(key))
+            .map(|&(_, value)| value)
+    };
+
+    if rule_part("FREQ") != Some("WEEKLY") || rule_part("INTERVAL").is_some_and(|i| i != "1") {
+        return Ok(vec![]);
+    }
+
+    let dtstart = find("DTSTART").ok_or(IcalError::MissingProperty {
+        line: begin,
+        property: "DTSTART",
+    })?;
+    if dtstart.params.to_ascii_uppercase().contains("VALUE=DATE") && !dtstart.value.contains('T') {
+        // All day event
+        return Ok(vec![]);
+    }
+    let start = local_date_time(dtstart, utc_offset)?;
+    // Week days in the rule refer to the date as written
+    let written_start = date_time_value(dtstart.value).ok_or_else(|| invalid(dtstart))?;
+    let day_shift = (start.date() - written_start.date()).num_days();
+
+    let end = match (find("DTEND"), find("DURATION")) {
+        (Some(dtend), _) => local_date_time(dtend, utc_offset)?,
+        (None, Some(duration)) => {
+            start + duration_value(duration.value).ok_or_else(|| invalid(duration))?
+        }
+        (None, None) => {
+            return Err(IcalError::MissingProperty {
+                line: begin,
+                property: "DTEND",
+            })
+        }
+    };
+
+    // Ending right at midnight is fine, it becomes the last second of the day
+    let ends_at_midnight = end.date() == start.date() + Duration::days(1)
+        && end.time().num_seconds_from_midnight() == 0;
+    if end <= start || (end.date() != start.date() && !ends_at_midnight) {
+        return Ok(vec![]);
+    }
+    let time_range = (
+        start.time(),
+        if ends_at_midnight {
+            NaiveTime::from_hms_opt(23, 59, 59).unwrap()
+        } else {
+            end.time()
+        },
+    );
+
+    let days = match rule_part("BYDAY") {
+        Some(codes) => codes
+            .split(',')
+            .map(|code| {
+                let day = Day::iter()
+                    .position(|day| weekday_code(day).eq_ignore_ascii_case(code.trim()))
+                    .ok_or_else(|| invalid(rrule))?;
+                let shifted = (day as i64 + day_shift).rem_euclid(7) as usize;
+                Ok(Day::iter().nth(shifted).unwrap())
+            })
+            .collect::<Result<Vec<_>, _>>()?,
+        None => Day::iter()
+            .filter(|&day| day as u32 == start.weekday().num_days_from_monday())
+            .collect(),
+    };
+
+    let summary = find("SUMMARY")
+        .map(|summary| unescape(summary.value))
+        .unwrap_or_default();
+
+    Ok(days
+        .into_iter()
+        .map(|day| WeeklyEvent {
+            summary: summary.clone(),
+            day,
+            time_range,
+        })
+        .collect())
+}
+
+/// Parses a DATE-TIME value, such as "20210111T080000" or "20210111T140000Z",
+/// as written.
+fn date_time_value(value: &str) -> Option<NaiveDateTime> {
+    NaiveDateTime::parse_from_str(value.trim_end_matches('Z'), "%Y%m%dT%H%M%S").ok()
+}
+
+/// Value of a DATE-TIME property, with UTC times converted to `utc_offset`.
+fn local_date_time(
+    property: &Property,
+    utc_offset: Option<FixedOffset>,
+) -> Result<NaiveDateTime, IcalError> {
+    let value = date_time_value(property.value).ok_or_else(|| IcalError::InvalidValue {
+        line: property.line,
+        value: property.value.to_string(),
+    })?;
+
+    if !property.value.ends_with('Z') {
+        return Ok(value);
+    }
+    let offset = utc_offset.ok_or(IcalError::UtcTime {
+        line: property.line,
+    })?;
+    Ok(value + Duration::seconds(offset.local_minus_utc().into()))
+}
+
+/// Parses a DURATION value, such as "PT1H30M" or "P1W".
+fn duration_value(value: &str) -> Option<Duration> {
+    let value = value.strip_prefix('+').unwrap_or(value).strip_prefix('P')?;
+    let (date, time) = value.split_once('T').unwrap_or((value, ""));
+
+    let mut seconds = 0;
+    for (part, units) in [
+        (date, &[('W', 604800), ('D', 86400)][..]),
+        (time, &[('H', 3600), ('M', 60), ('S', 1)][..]),
+    ] {
+        let mut rest = part;
+        for &(unit, length) in units {
+            if let Some((amount, tail)) = rest.split_once(unit) {
+                seconds += amount.parse::<i64>().ok()? * length;
+                rest = tail;
+            }
+        }
+        if !rest.is_empty() {
+            return None;
+        }
+    }
+
+    Some(Duration::seconds(seconds))
+}
+
+/// Undoes `escape`.
+fn unescape(text: &str) -> String {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') | Some('N') => unescaped.push('\n'),
+            Some(escaped) => unescaped.push(escaped),
+            None => unescaped.push('\\'),
+        }
+    }
+    unescaped
+}
+
+/// First date on or after `date` that falls on `day`.
+fn first_weekday(date: NaiveDate, day: Day) -> NaiveDate {
+    let offset = (day as i64 - date.weekday().num_days_from_monday() as i64).rem_euclid(7);
+    date + Duration::days(offset)
+}
+
+fn weekday_code(day: Day) -> &'static str {
+    match day {
+        Day::MONDAY => "MO",
+        Day::TUESDAY => "TU",
+        Day::WEDNESDAY => "WE",
+        Day::THURSDAY => "TH",
+        Day::FRIDAY => "FR",
+        Day::SATURDAY => "SA",
+        Day::SUNDAY => "SU",
+    }
+}
+
+fn date_value(date: NaiveDate) -> String {
+    date.format("%Y%m%d").to_string()
+}
+
+fn date_time(date: NaiveDate, time: NaiveTime) -> String {
+    format!("{}T{}", date_value(date), time.format("%H%M%S"))
+}
+
+/// VTIMEZONE component describing `tz` from `term_start` to `term_end`, with
+/// one observance for the offset in effect at the start and one for every
+/// change of offset after it.
+fn vtimezone(tz: Tz, term_start: NaiveDate, term_end: NaiveDate) -> Vec<String> {
+    let offset = |utc: NaiveDateTime| {
+        let offset = tz.offset_from_utc_datetime(&utc);
+        (
+            offset.fix(),
+            offset.dst_offset(),
+            offset.abbreviation().map(String::from),
+        )
+    };
+
+    // A day on each side covers the whole term wherever the zone is
+    let mut utc = (term_start - Duration::days(1)).and_time(NaiveTime::MIN);
+    let end = (term_end + Duration::days(2)).and_time(NaiveTime::MIN);
+
+    let mut current = offset(utc);
+    let mut lines = vec!["BEGIN:VTIMEZONE".to_string(), format!("TZID:{}", tz)];
+    lines.extend(observance(utc + current.0, &current, &current));
+
+    while utc < end {
+        let next = utc + Duration::hours(1);
+        if offset(next) != current {
+            // Narrow the change down to the minute
+            let (mut before, mut after) = (utc, next);
+            while after - before > Duration::minutes(1) {
+                let middle = before + (after - before) / 2;
+                if offset(middle) == current {
+                    before = middle;
+                } else {
+                    after = middle;
+                }
+            }
+
+            let changed = offset(after);
+            lines.extend(observance(after + current.0, &current, &changed));
+            current = changed;
+        }
+        utc = next;
+    }
+
+    lines.push("END:VTIMEZONE".to_string());
+    lines
+}
+
+/// STANDARD or DAYLIGHT component for going from the offset `from` to `to`
+/// at the local time `start`.
+fn observance(
+    start: NaiveDateTime,
+    from: &(FixedOffset, Duration, Option<String>),
+    to: &(FixedOffset, Duration, Option<String>),
+) -> Vec<String> {
+    let kind = if to.1.is_zero() {
+        "STANDARD"
+    } else {
+        "DAYLIGHT"
+    };
+    let mut lines = vec![
+        format!("BEGIN:{}", kind),
+        format!("DTSTART:{}", date_time(start.date(), start.time())),
+        format!("TZOFFSETFROM:{}", utc_offset_value(from.0)),
+        format!("TZOFFSETTO:{}", utc_offset_value(to.0)),
+    ];
+    if let Some(name) = &to.2 {
+        lines.push(format!("TZNAME:{}", escape(name)));
+    }
+    lines.push(format!("END:{}", kind));
+    lines
+}
+
+/// Formats `offset` as a UTC-OFFSET value, i.e. "-0600".
+fn utc_offset_value(offset: FixedOffset) -> String {
+    let seconds = offset.local_minus_utc();
+    let sign = if seconds < 0 { '-' } else { '+' };
+    let minutes = seconds.abs() / 60;
+    format!("{}{:02}{:02}", sign, minutes / 60, minutes % 60)
+}
+
+/// Escapes the characters that have a meaning in TEXT values.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' | ';' | ',' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '\n' => escaped.push_str("\\n"),
+            '\r' => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Splits `line` into lines of at most `MAX_LINE_LENGTH` octets, each
+/// continuation starting with a space, and terminates it with CRLF.
+fn fold(line: &str) -> String {
+    let mut folded = String::with_capacity(line.len() + 2);
+    let mut length = 0;
+
+    for c in line.chars() {
+        if length + c.len_utf8() > MAX_LINE_LENGTH {
+            folded.push_str("\r\n ");
+            // The leading space counts toward the length of the line
+            length = 1;
+        }
+        folded.push(c);
+        length += c.len_utf8();
+    }
+
+    folded.push_str("\r\n");
+    folded
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::utils::time;
+
+    fn date(value: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(value, "%Y-%m-%d").unwrap()
+    }
+
+    #[test]
+    fn test_to_ical() {
+        let mut time_values: [Vec<_>; 7] = Default::default();
+        time_values[Day::MONDAY as usize] = vec![(time("08:00"), time("10:00"))];
+        time_values[Day::WEDNESDAY as usize] = vec![
+            (time("08:00"), time("09:00")),
+            (time("16:00"), time("17:30")),
+        ];
+        let grid = Grid::new(7, time_values, "Physics, group 3".to_string()).unwrap();
+
+        let mut schedule = Schedule::new();
+        schedule.try_merge(&grid).unwrap();
+
+        // Thursday 2021-01-07 to Monday 2021-02-01, with Wednesday 2021-01-13 off
+        let stamp = Utc.with_ymd_and_hms(2021, 1, 2, 12, 30, 0).unwrap();
+        let ical = schedule.to_ical(
+            date("2021-01-07"),
+            date("2021-02-01"),
+            chrono_tz::America::Mexico_City,
+            &[date("2021-01-13"), date("2021-01-14")],
+            "physics-3",
+            stamp,
+            |grid| EventText {
+                summary: grid.data().clone(),
+                description: None,
+                location: Some("Building A; room 2".to_string()),
+            },
+        );
+
+        assert!(ical.starts_with("BEGIN:VCALENDAR\r\n"));
+        assert!(ical.ends_with("END:VCALENDAR\r\n"));
+        assert_eq!(ical.matches("BEGIN:VEVENT").count(), 3);
+
+        let lines: Vec<_> = ical.split("\r\n").collect();
+        assert!(lines.contains(&"DTSTART;TZID=America/Mexico_City:20210111T080000"));
+        assert!(lines.contains(&"DTEND;TZID=America/Mexico_City:20210111T100000"));
+        assert!(lines.contains(&"RRULE:FREQ=WEEKLY;BYDAY=MO;COUNT=4"));
+        assert!(lines.contains(&"DTSTART;TZID=America/Mexico_City:20210113T160000"));
+        assert!(lines.contains(&"RRULE:FREQ=WEEKLY;BYDAY=WE;COUNT=3"));
+        assert!(lines.contains(&"SUMMARY:Physics\\, group 3"));
+        assert!(lines.contains(&"LOCATION:Building A\\; room 2"));
+        assert!(lines.contains(&"UID:physics-3-20210107-0-0-0@schedule-engine"));
+
+        assert!(lines.contains(&"DTSTAMP:20210102T123000Z"));
+
+        // The zone is defined before any event refers to it
+        let zone = lines.iter().position(|line| *line == "BEGIN:VTIMEZONE");
+        assert!(zone < lines.iter().position(|line| *line == "BEGIN:VEVENT"));
+        assert!(lines.contains(&"TZID:America/Mexico_City"));
+        assert!(lines.contains(&"TZOFFSETTO:-0600"));
+        assert_eq!(ical.matches("BEGIN:STANDARD").count(), 1);
+        assert_eq!(ical.matches("BEGIN:DAYLIGHT").count(), 0);
+
+        // Holidays only apply to the days they fall on
+        assert_eq!(
+            lines
+                .iter()
+                .filter(|line| line.starts_with("EXDATE"))
+                .collect::<Vec<_>>(),
+            vec![
+                &"EXDATE;TZID=America/Mexico_City:20210113T080000",
+                &"EXDATE;TZID=America/Mexico_City:20210113T160000",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_read_weekly_events() {
+        let input = "BEGIN:VCALENDAR\r
+VERSION:2.0\r
+BEGIN:VEVENT\r
+SUMMARY:Work\\, downtown\r
+DTSTART:20210112T140000\r
+DTEND:20210112T180000\r
+RRULE:FREQ=WEEKLY;BYDAY=TU,TH;UNTIL=20210601T000000Z\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+SUMMARY:Gy\r
+ m\r
+DTSTART:20210111T070000\r
+DURATION:PT1H30M\r
+RRULE:FREQ=WEEKLY\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+SUMMARY:Overtime\r
+DTSTART:20210112T170000\r
+DTEND:20210112T190000\r
+RRULE:FREQ=WEEKLY;BYDAY=TU\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+SUMMARY:Dentist\r
+DTSTART:20210113T100000\r
+DTEND:20210113T110000\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+SUMMARY:Every other week\r
+DTSTART:20210113T100000\r
+DTEND:20210113T110000\r
+RRULE:FREQ=WEEKLY;INTERVAL=2\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+SUMMARY:Holiday\r
+DTSTART;VALUE=DATE:20210115\r
+RRULE:FREQ=WEEKLY\r
+END:VEVENT\r
+END:VCALENDAR\r
+";
+        let events = read_weekly_events(input, None).unwrap();
+        let summarized: Vec<_> = events
+            .iter()
+            .map(|event| (event.summary.as_str(), event.day, event.time_range))
+            .collect();
+
+        assert_eq!(
+            summarized,
+            vec![
+                (
+                    "Work, downtown",
+                    Day::TUESDAY,
+                    (time("14:00"), time("18:00"))
+                ),
+                (
+                    "Work, downtown",
+                    Day::THURSDAY,
+                    (time("14:00"), time("18:00"))
+                ),
+                ("Gym", Day::MONDAY, (time("07:00"), time("08:30"))),
+                ("Overtime", Day::TUESDAY, (time("17:00"), time("19:00"))),
+            ]
+        );
+
+        let availability = busy_availability(&events);
+        assert_eq!(
+            availability.forbidden[Day::MONDAY as usize],
+            vec![(time("07:00"), time("08:30"))]
+        );
+        assert_eq!(
+            availability.forbidden[Day::TUESDAY as usize],
+            vec![(time("14:00"), time("19:00"))]
+        );
+        assert_eq!(
+            availability.forbidden[Day::THURSDAY as usize],
+            vec![(time("14:00"), time("18:00"))]
+        );
+        assert!(availability.forbidden[Day::WEDNESDAY as usize].is_empty());
+        assert_eq!(availability.allowed, Availability::new().allowed);
+    }
+
+    #[test]
+    fn test_read_weekly_events_errors() {
+        let input = "BEGIN:VEVENT\nDTSTART:tomorrow\nRRULE:FREQ=WEEKLY\nEND:VEVENT\n";
+        assert_eq!(
+            read_weekly_events(input, None).unwrap_err(),
+            IcalError::InvalidValue {
+                line: 2,
+                value: "tomorrow".to_string()
+            }
+        );
+
+        let input = "BEGIN:VEVENT\nDTSTART:20210111T070000\nRRULE:FREQ=WEEKLY\nEND:VEVENT\n";
+        assert_eq!(
+            read_weekly_events(input, None).unwrap_err(),
+            IcalError::MissingProperty {
+                line: 1,
+                property: "DTEND"
+            }
+        );
+
+        // Properties of an alarm don't belong to its event
+        let input = "BEGIN:VEVENT
+DTSTART:20210111T070000
+RRULE:FREQ=WEEKLY
+BEGIN:VALARM
+ACTION:DISPLAY
+SUMMARY:Reminder
+DURATION:PT5M
+END:VALARM
+END:VEVENT
+";
+        assert_eq!(
+            read_weekly_events(input, None).unwrap_err(),
+            IcalError::MissingProperty {
+                line: 1,
+                property: "DTEND"
+            }
+        );
+
+        let input = "BEGIN:VEVENT\nDTSTART:20210111T070000\n";
+        assert_eq!(
+            read_weekly_events(input, None).unwrap_err(),
+            IcalError::UnexpectedEnd { line: 1 }
+        );
+
+        let input = "BEGIN:VEVENT\nDTSTART:20210112T200000Z\nDURATION:PT2H\nRRULE:FREQ=WEEKLY\nEND:VEVENT\n";
+        assert_eq!(
+            read_weekly_events(input, None).unwrap_err(),
+            IcalError::UtcTime { line: 2 }
+        );
+    }
+
+    #[test]
+    fn test_read_utc_events() {
+        // Tuesdays at 20:00 UTC are Tuesdays at 14:00 in Mexico City, while
+        // Tuesdays at 02:00 UTC are still Mondays there
+        let input = "BEGIN:VEVENT
+DTSTART:20210112T200000Z
+DTEND:20210112T220000Z
+RRULE:FREQ=WEEKLY;BYDAY=TU
+END:VEVENT
+BEGIN:VEVENT
+DTSTART:20210112T020000Z
+DTEND:20210112T030000Z
+RRULE:FREQ=WEEKLY;BYDAY=TU,FR
+END:VEVENT
+";
+        let mexico_city = FixedOffset::west_opt(6 * 3600);
+        let events: Vec<_> = read_weekly_events(input, mexico_city)
+            .unwrap()
+            .into_iter()
+            .map(|event| (event.day, event.time_range))
+            .collect();
+
+        assert_eq!(
+            events,
+            vec![
+                (Day::TUESDAY, (time("14:00"), time("16:00"))),
+                (Day::MONDAY, (time("20:00"), time("21:00"))),
+                (Day::THURSDAY, (time("20:00"), time("21:00"))),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_ical_round_trip() {
+        let mut time_values: [Vec<_>; 7] = Default::default();
+        time_values[Day::FRIDAY as usize] = vec![(time("09:00"), time("11:00"))];
+        let grid = Grid::new(1, time_values, ()).unwrap();
+
+        let mut schedule = Schedule::new();
+        schedule.try_merge(&grid).unwrap();
+
+        let ical = schedule.to_ical(
+            date("2021-01-07"),
+            date("2021-02-01"),
+            chrono_tz::Europe::Madrid,
+            &[],
+            "friday",
+            Utc.with_ymd_and_hms(2021, 1, 2, 12, 30, 0).unwrap(),
+            |_| EventText {
+                summary: "Line one\nline; two".to_string(),
+                ..EventText::default()
+            },
+        );
+
+        assert_eq!(
+            read_weekly_events(&ical, None).unwrap(),
+            vec![WeeklyEvent {
+                summary: "Line one\nline; two".to_string(),
+                day: Day::FRIDAY,
+                time_range: (time("09:00"), time("11:00")),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_vtimezone() {
+        // Daylight saving time starts on 2021-03-14 at 02:00 in New York
+        let lines = vtimezone(
+            chrono_tz::America::New_York,
+            date("2021-03-01"),
+            date("2021-03-31"),
+        );
+
+        assert_eq!(
+            lines,
+            vec![
+                "BEGIN:VTIMEZONE",
+                "TZID:America/New_York",
+                "BEGIN:STANDARD",
+                "DTSTART:20210227T190000",
+                "TZOFFSETFROM:-0500",
+                "TZOFFSETTO:-0500",
+                "TZNAME:EST",
+                "END:STANDARD",
+                "BEGIN:DAYLIGHT",
+                "DTSTART:20210314T020000",
+                "TZOFFSETFROM:-0500",
+                "TZOFFSETTO:-0400",
+                "TZNAME:EDT",
+                "END:DAYLIGHT",
+                "END:VTIMEZONE",
+            ]
+        );
+        assert_eq!(
+            utc_offset_value(FixedOffset::east_opt(20700).unwrap()),
+            "+0545"
+        );
+    }
+
+    #[test]
+    fn test_fold() {
+        let line = "SUMMARY:".to_string() + &"a".repeat(100);
+        let folded = fold(&line);
+        let lines: Vec<_> = folded.trim_end().split("\r\n").collect();
+
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].len(), MAX_LINE_LENGTH);
+        assert!(lines[1].starts_with(' '));
+        assert_eq!(lines.concat().replacen(' ', "", 1), line);
+    }
+}
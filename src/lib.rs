@@ -15,19 +15,338 @@
  * You should have received a copy of the GNU General Public License
  * along with this program.  If not, see <http://www.gnu.org/licenses/>.
  */
+pub mod availability;
+pub mod compat;
 pub mod engine;
+pub mod error;
 pub mod grid;
+pub mod ical;
+pub mod mask;
+pub mod observer;
+pub mod parse;
+pub mod score;
+pub mod travel;
 pub mod utils;
+use crate::engine::{
+    compatibility_matrix, count_schedules, engine_main, search_with_progress, seed_conflicts,
+    CancelToken, EngineParams, Solver,
+};
+use crate::error::ScheduleError;
+use crate::grid::{GapRule, Grid, Pool, Schedule};
+use crate::ical::{busy_availability, read_weekly_events, EventText};
+use crate::travel::TravelTimes;
+use chrono::{FixedOffset, NaiveDate, Utc};
+use chrono_tz::Tz;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+use serde_wasm_bindgen::Serializer;
+use std::cell::RefCell;
+use std::fmt::Display;
 use wasm_bindgen::prelude::*;
 
-#[wasm_bindgen]
-extern "C" {
-    fn alert(s: &str);
-}
-
 #[macro_export]
 macro_rules! log {
     ( $( $t:tt )* ) => {
         web_sys::console::log_1(&format!( $( $t )* ).into());
     }
 }
+
+/// Runs the engine from JavaScript.
+///
+/// `params` must be an object shaped like `EngineParams`, where pool ids and
+/// grid `data` can be arbitrary JSON values. Returns an array with all valid
+/// schedules, or throws an `Error` carrying the reason the engine failed.
+/// The error's `kind` and `details` tell which `ScheduleError` it was.
+#[wasm_bindgen(js_name = engineMain)]
+pub fn engine_main_js(params: JsValue) -> Result<JsValue, JsValue> {
+    utils::set_panic_hook();
+
+    let params: EngineParams<Value, Value> =
+        serde_wasm_bindgen::from_value(params).map_err(js_error)?;
+
+    let schedules = engine_main(params).map_err(schedule_error)?;
+
+    // Serialize maps as plain objects so that `data` comes back the way it was sent.
+    schedules
+        .serialize(&Serializer::json_compatible())
+        .map_err(js_error)
+}
+
+/// Same as `engineMain`, but returns `{ schedules, exhaustive, progress }` and
+/// accepts the `max_results` and `max_nodes` limits.
+///
+/// When given, `on_progress` is called with `{ combinations_done,
+/// combinations_total, nodes_visited }` as the search advances. Returning
+/// `false` from it cancels the search.
+#[wasm_bindgen(js_name = engineSearch)]
+pub fn engine_search_js(
+    params: JsValue,
+    on_progress: Option<js_sys::Function>,
+) -> Result<JsValue, JsValue> {
+    utils::set_panic_hook();
+
+    let mut params: EngineParams<Value, Value> =
+        serde_wasm_bindgen::from_value(params).map_err(js_error)?;
+
+    let cancel = CancelToken::new();
+    params.cancel = Some(cancel.clone());
+
+    let outcome = search_with_progress(params, move |progress| {
+        let Some(on_progress) = on_progress.as_ref() else {
+            return;
+        };
+        let Ok(progress) = progress.serialize(&Serializer::json_compatible()) else {
+            return;
+        };
+        let keep_going = on_progress.call1(&JsValue::NULL, &progress);
+        if keep_going.is_ok_and(|value| value.as_bool() == Some(false)) {
+            cancel.cancel();
+        }
+    })
+    .map_err(schedule_error)?;
+
+    outcome
+        .serialize(&Serializer::json_compatible())
+        .map_err(js_error)
+}
+
+/// Counts the schedules `engineMain` would return for `params`, stopping at
+/// `cap` when given.
+#[wasm_bindgen(js_name = countSchedules)]
+pub fn count_schedules_js(params: JsValue, cap: Option<f64>) -> Result<f64, JsValue> {
+    utils::set_panic_hook();
+
+    let params: EngineParams<Value, Value> =
+        serde_wasm_bindgen::from_value(params).map_err(js_error)?;
+
+    let count = count_schedules(params, cap.map(|cap| cap as u64)).map_err(schedule_error)?;
+    Ok(count as f64)
+}
+
+/// Search that runs in chunks, so that it doesn't block the event loop.
+///
+/// ```js
+/// const solver = new Solver(params);
+/// function tick() {
+///   const { schedules, done, progress } = solver.step(10000);
+///   render(schedules, progress);
+///   if (!done) requestAnimationFrame(tick);
+/// }
+/// ```
+#[wasm_bindgen(js_name = Solver)]
+pub struct SolverJs {
+    solver: Solver<Value, Value>,
+}
+
+#[wasm_bindgen(js_class = Solver)]
+impl SolverJs {
+    /// `params` is shaped like in `engineMain`. Throws if they are invalid.
+    #[wasm_bindgen(constructor)]
+    pub fn new(params: JsValue) -> Result<SolverJs, JsValue> {
+        utils::set_panic_hook();
+
+        let params: EngineParams<Value, Value> =
+            serde_wasm_bindgen::from_value(params).map_err(js_error)?;
+
+        Ok(SolverJs {
+            solver: Solver::new(params).map_err(schedule_error)?,
+        })
+    }
+
+    /// Visits at most `max_nodes` partial schedules and returns
+    /// `{ schedules, done, exhaustive, progress }`.
+    pub fn step(&mut self, max_nodes: f64) -> Result<JsValue, JsValue> {
+        self.solver
+            .step(max_nodes as u64)
+            .serialize(&Serializer::json_compatible())
+            .map_err(js_error)
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn done(&self) -> bool {
+        self.solver.is_done()
+    }
+
+    /// Positions of the seeds left out by the `largest_compatible` seed policy.
+    #[wasm_bindgen(getter, js_name = droppedSeeds)]
+    pub fn dropped_seeds(&self) -> Result<JsValue, JsValue> {
+        self.solver
+            .dropped_seeds()
+            .serialize(&Serializer::json_compatible())
+            .map_err(js_error)
+    }
+
+    /// `[pool, grid]` indices of the grids left out by the availability.
+    #[wasm_bindgen(getter, js_name = unavailableGrids)]
+    pub fn unavailable_grids(&self) -> Result<JsValue, JsValue> {
+        self.solver
+            .unavailable_grids()
+            .serialize(&Serializer::json_compatible())
+            .map_err(js_error)
+    }
+}
+
+/// Lists every pair of seeds that clash, as
+/// `{ seeds, pools, day, interval, required_minutes }`.
+///
+/// `seeds`, `gap` and `travel_times` are shaped like the fields of
+/// `EngineParams` of the same name, the last two being optional.
+#[wasm_bindgen(js_name = seedConflicts)]
+pub fn seed_conflicts_js(
+    seeds: JsValue,
+    gap: JsValue,
+    travel_times: JsValue,
+) -> Result<JsValue, JsValue> {
+    utils::set_panic_hook();
+
+    let seeds: Vec<Grid<Value, Value>> = serde_wasm_bindgen::from_value(seeds).map_err(js_error)?;
+    let gap = optional::<GapRule<Value, Value>>(gap)?.with_travel_times(optional(travel_times)?);
+
+    seed_conflicts(&seeds, &gap)
+        .serialize(&Serializer::json_compatible())
+        .map_err(js_error)
+}
+
+/// Writes a schedule returned by `engineMain` as an iCalendar file, with
+/// times in the `tz` time zone, e.g. "America/Mexico_City".
+///
+/// Dates are formatted as "YYYY-MM-DD", and `holidays` is an array of them.
+/// Event UIDs start with `uid_prefix`, which should tell schedules apart.
+/// `describe` is called with the `data` of each grid and must return
+/// `{ summary, description, location }`, where the last two are optional.
+#[wasm_bindgen(js_name = scheduleToIcal)]
+pub fn schedule_to_ical_js(
+    schedule: JsValue,
+    term_start: &str,
+    term_end: &str,
+    tz: &str,
+    holidays: JsValue,
+    uid_prefix: &str,
+    describe: js_sys::Function,
+) -> Result<String, JsValue> {
+    utils::set_panic_hook();
+
+    let schedule: Schedule<Value, Value> =
+        serde_wasm_bindgen::from_value(schedule).map_err(js_error)?;
+    let date = |value: &str| NaiveDate::parse_from_str(value, "%Y-%m-%d").map_err(js_error);
+    let holidays: Vec<String> = serde_wasm_bindgen::from_value(holidays).map_err(js_error)?;
+    let holidays = holidays
+        .iter()
+        .map(|holiday| date(holiday))
+        .collect::<Result<Vec<_>, _>>()?;
+    let tz: Tz = tz.parse().map_err(js_error)?;
+
+    // The first error thrown by `describe`, if any
+    let error = RefCell::new(None);
+
+    let ical = schedule.to_ical(
+        date(term_start)?,
+        date(term_end)?,
+        tz,
+        &holidays,
+        uid_prefix,
+        Utc::now(),
+        |grid| {
+            let text = grid
+                .data()
+                .serialize(&Serializer::json_compatible())
+                .map_err(js_error)
+                .and_then(|data| describe.call1(&JsValue::NULL, &data))
+                .and_then(|text| {
+                    serde_wasm_bindgen::from_value::<EventText>(text).map_err(js_error)
+                });
+
+            text.unwrap_or_else(|err| {
+                error.borrow_mut().get_or_insert(err);
+                EventText::default()
+            })
+        },
+    );
+
+    match error.into_inner() {
+        Some(err) => Err(err),
+        None => Ok(ical),
+    }
+}
+
+/// Reads the weekly recurring events of an iCalendar file into an
+/// availability that keeps classes away from them, to be passed as
+/// `EngineParams::availability`.
+///
+/// UTC times are converted to `utc_offset_minutes`, and rejected when it is
+/// missing.
+#[wasm_bindgen(js_name = availabilityFromIcal)]
+pub fn availability_from_ical_js(
+    input: &str,
+    utc_offset_minutes: Option<i32>,
+) -> Result<JsValue, JsValue> {
+    utils::set_panic_hook();
+
+    let utc_offset = match utc_offset_minutes {
+        Some(minutes) => Some(
+            FixedOffset::east_opt(minutes * 60)
+                .ok_or_else(|| js_error(format!("Invalid UTC offset: {}", minutes)))?,
+        ),
+        None => None,
+    };
+
+    let events = read_weekly_events(input, utc_offset).map_err(js_error)?;
+    busy_availability(&events)
+        .serialize(&Serializer::json_compatible())
+        .map_err(js_error)
+}
+
+/// Tells which grids can be taken together, so that incompatible groups can be
+/// greyed out without running the engine.
+///
+/// `pool_list`, `gap` and `travel_times` are shaped like the fields of
+/// `EngineParams` of the same name, the last two being optional. The result
+/// is indexed as `result[pool_a][grid_a][pool_b][grid_b]`.
+#[wasm_bindgen(js_name = compatibilityMatrix)]
+pub fn compatibility_matrix_js(
+    pool_list: JsValue,
+    gap: JsValue,
+    travel_times: JsValue,
+) -> Result<JsValue, JsValue> {
+    utils::set_panic_hook();
+
+    let pool_list: Vec<Pool<Value, Value>> =
+        serde_wasm_bindgen::from_value(pool_list).map_err(js_error)?;
+    let gap = optional::<GapRule<Value, Value>>(gap)?;
+    let travel_times = optional::<TravelTimes>(travel_times)?;
+
+    compatibility_matrix(&pool_list, &gap, &travel_times)
+        .to_nested()
+        .serialize(&Serializer::json_compatible())
+        .map_err(js_error)
+}
+
+/// Reads an optional argument, which is the default value when missing.
+fn optional<T: Default + DeserializeOwned>(value: JsValue) -> Result<T, JsValue> {
+    if value.is_undefined() || value.is_null() {
+        return Ok(T::default());
+    }
+    serde_wasm_bindgen::from_value(value).map_err(js_error)
+}
+
+fn js_error<E: Display>(err: E) -> JsValue {
+    js_sys::Error::new(&err.to_string()).into()
+}
+
+/// Same as `js_error`, but the `kind` and `details` of the failure are set on
+/// the error too, e.g. `{ kind: "SeedConflict", details: { seed: 1, ... } }`.
+fn schedule_error(err: ScheduleError<Value>) -> JsValue {
+    let error = js_error(&err);
+
+    if let Ok(fields) = err.serialize(&Serializer::json_compatible()) {
+        for key in ["kind", "details"] {
+            let key = JsValue::from_str(key);
+            if let Ok(value) = js_sys::Reflect::get(&fields, &key) {
+                let _ = js_sys::Reflect::set(&error, &key, &value);
+            }
+        }
+    }
+
+    error
+}
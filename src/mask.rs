@@ -0,0 +1,108 @@
+/**
+ * src/mask.rs
+ * Copyright (c) 2021 Carlos Carral <carloscarral13@gmail.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+use crate::grid::TimeRange;
+use chrono::Timelike;
+
+/// Length of each slot of a mask.
+pub const SLOT_MINUTES: u32 = 5;
+const SLOT_SECONDS: u32 = SLOT_MINUTES * 60;
+const SLOTS_PER_DAY: usize = 24 * 60 / SLOT_MINUTES as usize;
+const WORDS_PER_DAY: usize = SLOTS_PER_DAY.div_ceil(64);
+
+/// Occupancy of a whole week, one bit per `SLOT_MINUTES` slot.
+///
+/// A range marks every slot it touches, even partially, so two masks that
+/// don't intersect are guaranteed to be compatible. The opposite is only true
+/// when every time falls on a slot boundary: intersecting masks have to be
+/// confirmed by comparing the actual time ranges.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct WeekMask {
+    days: [[u64; WORDS_PER_DAY]; 7],
+}
+
+impl WeekMask {
+    pub fn new(time_values: &[Vec<TimeRange>; 7]) -> Self {
+        let mut mask = WeekMask::default();
+        for (day, ranges) in time_values.iter().enumerate() {
+            for range in ranges {
+                mask.insert(day, range);
+            }
+        }
+        mask
+    }
+
+    fn insert(&mut self, day: usize, &(start, end): &TimeRange) {
+        let first = (start.num_seconds_from_midnight() / SLOT_SECONDS) as usize;
+        let last = end.num_seconds_from_midnight().div_ceil(SLOT_SECONDS) as usize;
+
+        // An empty range still marks the slot it falls in, since it
+        // clashes with any range that contains it.
+        for slot in first..last.max(first + 1).min(SLOTS_PER_DAY) {
+            self.days[day][slot / 64] |= 1 << (slot % 64);
+        }
+    }
+
+    pub fn intersects(&self, other: &WeekMask) -> bool {
+        self.days
+            .iter()
+            .flatten()
+            .zip(other.days.iter().flatten())
+            .any(|(mine, other)| mine & other != 0)
+    }
+
+    pub fn union(&self, other: &WeekMask) -> WeekMask {
+        let mut mask = *self;
+        for (mine, other) in mask
+            .days
+            .iter_mut()
+            .flatten()
+            .zip(other.days.iter().flatten())
+        {
+            *mine |= other;
+        }
+        mask
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::grid::Grid;
+    use crate::utils::monday;
+
+    fn mask(begin: &str, end: &str) -> WeekMask {
+        *Grid::from_vec(1, monday(begin, end), "%H:%M", ())
+            .unwrap()
+            .mask()
+    }
+
+    #[test]
+    fn test_intersects() {
+        assert!(!mask("08:00", "10:00").intersects(&mask("10:00", "12:00")));
+        assert!(mask("08:00", "10:00").intersects(&mask("09:55", "12:00")));
+        assert!(mask("23:00", "23:59").intersects(&mask("23:58", "23:59")));
+
+        // Both ranges touch the 10:00 - 10:05 slot
+        assert!(mask("08:00", "10:03").intersects(&mask("10:03", "12:00")));
+
+        let union = mask("08:00", "10:00").union(&mask("16:00", "17:00"));
+        assert!(union.intersects(&mask("16:30", "16:45")));
+        assert!(!union.intersects(&mask("12:00", "13:00")));
+        assert!(!WeekMask::default().intersects(&union));
+    }
+}
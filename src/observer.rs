@@ -0,0 +1,81 @@
+/**
+ * src/observer.rs
+ * Copyright (c) 2021 Carlos Carral <carloscarral13@gmail.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+use crate::grid::{Grid, Schedule};
+use core::fmt::Debug;
+
+/// Gets told what the engine is doing while it searches, for diagnostics.
+/// Every method does nothing by default.
+pub trait EngineObserver<Id: Eq + Clone, D: Clone> {
+    /// Seed number `index` has been merged into the starting schedule.
+    fn seed_merged(&mut self, _index: usize, _seed: &Grid<Id, D>) {}
+
+    /// The search moves on to a new combination of pools, given as indices
+    /// into `EngineParams::pool_list`.
+    fn combination(&mut self, _pools: &[usize]) {}
+
+    /// The search won't descend into `partial`, as nothing below it can be
+    /// a valid or good enough schedule. Its last grid is the one that caused it.
+    fn pruned(&mut self, _partial: &Schedule<Id, D>) {}
+
+    /// `schedule` is valid.
+    fn leaf(&mut self, _schedule: &Schedule<Id, D>) {}
+}
+
+/// Observer that ignores everything.
+impl<Id: Eq + Clone, D: Clone> EngineObserver<Id, D> for () {}
+
+/// Observer that writes every event to the browser console on wasm, and to
+/// stderr everywhere else.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct LogObserver;
+
+impl<Id, D> EngineObserver<Id, D> for LogObserver
+where
+    Id: Eq + Clone + Debug,
+    D: Clone,
+{
+    fn seed_merged(&mut self, index: usize, seed: &Grid<Id, D>) {
+        emit(format!("seed {} merged (pool {:?})", index, seed.pool_id));
+    }
+
+    fn combination(&mut self, pools: &[usize]) {
+        emit(format!("combination {:?}", pools));
+    }
+
+    fn pruned(&mut self, partial: &Schedule<Id, D>) {
+        emit(format!("pruned at {:?}", pool_ids(partial)));
+    }
+
+    fn leaf(&mut self, schedule: &Schedule<Id, D>) {
+        emit(format!("leaf {:?}", pool_ids(schedule)));
+    }
+}
+
+fn pool_ids<Id: Eq + Clone, D: Clone>(schedule: &Schedule<Id, D>) -> Vec<&Id> {
+    schedule.grids().iter().map(|grid| &grid.pool_id).collect()
+}
+
+#[cfg(target_arch = "wasm32")]
+fn emit(message: String) {
+    crate::log!("{}", message);
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn emit(message: String) {
+    eprintln!("{}", message);
+}
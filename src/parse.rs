@@ -0,0 +1,134 @@
+/**
+ * src/parse.rs
+ * Copyright (c) 2021 Carlos Carral <carloscarral13@gmail.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+use crate::error::ScheduleError;
+use crate::grid::{Grid, Pool};
+use std::collections::HashMap;
+use std::io::Read;
+use thiserror::Error;
+
+/// Grid data read from a CSV file: every column that is neither the pool id
+/// nor a time column, keyed by its header.
+pub type CsvData = HashMap<String, String>;
+
+/// Describes where each field of a course offering lives in a CSV row.
+/// Columns are 0 based.
+pub struct CsvLayout {
+    /// Column holding the id of the pool (i.e: subject) each row belongs to.
+    pub pool_id_column: usize,
+    /// First of the 14 begin/end time columns, ordered as `Grid::from_vec` expects.
+    pub time_columns_start: usize,
+    /// Format of the time columns, as understood by `NaiveTime::parse_from_str`.
+    pub time_format: String,
+}
+
+impl Default for CsvLayout {
+    fn default() -> Self {
+        CsvLayout {
+            pool_id_column: 0,
+            time_columns_start: 1,
+            time_format: "%H:%M".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum ParseError {
+    #[error(transparent)]
+    Csv(#[from] csv::Error),
+
+    /// `row` is the line number in the input, counting the header as line 1.
+    #[error("Row {row}: missing column {column}")]
+    MissingColumn { row: u64, column: usize },
+
+    #[error("Row {row}, column {column}: {source}")]
+    InvalidGrid {
+        row: u64,
+        column: usize,
+        source: ScheduleError<String>,
+    },
+}
+
+/// Reads a CSV of course offerings (with a header row) and groups its rows
+/// into pools by pool id, in order of first appearance.
+pub fn read_pools<R: Read>(
+    reader: R,
+    layout: &CsvLayout,
+) -> Result<Vec<Pool<String, CsvData>>, ParseError> {
+    let mut reader = csv::Reader::from_reader(reader);
+    let headers = reader.headers()?.clone();
+
+    let time_columns = layout.time_columns_start..layout.time_columns_start + 14;
+    let mut pool_list: Vec<Pool<String, CsvData>> = vec![];
+
+    for record in reader.records() {
+        let record = record?;
+        let row = record.position().map_or(0, |position| position.line());
+
+        let field = |column: usize| {
+            record
+                .get(column)
+                .ok_or(ParseError::MissingColumn { row, column })
+        };
+
+        let pool_id = field(layout.pool_id_column)?.to_string();
+
+        let mut time_values: [String; 14] = Default::default();
+        for (time_value, column) in time_values.iter_mut().zip(time_columns.clone()) {
+            *time_value = field(column)?.to_string();
+        }
+
+        let data = headers
+            .iter()
+            .zip(record.iter())
+            .enumerate()
+            .filter(|(column, _)| {
+                *column != layout.pool_id_column && !time_columns.contains(column)
+            })
+            .map(|(_, (header, value))| (header.to_string(), value.to_string()))
+            .collect();
+
+        let grid = Grid::from_vec(pool_id.clone(), time_values, &layout.time_format, data)
+            .map_err(|source| {
+                // Point at the offending column of the whole row
+                let column = layout.time_columns_start
+                    + match &source {
+                        ScheduleError::MalformedSlice { index }
+                        | ScheduleError::InvalidTime { index, .. } => *index,
+                        ScheduleError::InvalidTimeRange { day, .. }
+                        | ScheduleError::OverlappingTimeRanges { day, .. } => *day as usize * 2,
+                        _ => 0,
+                    };
+                ParseError::InvalidGrid {
+                    row,
+                    column,
+                    source,
+                }
+            })?;
+
+        match pool_list.iter_mut().find(|pool| pool.pool_id == pool_id) {
+            Some(pool) => pool.push(grid),
+            None => {
+                let mut pool = Pool::new(pool_id);
+                pool.push(grid);
+                pool_list.push(pool);
+            }
+        }
+    }
+
+    Ok(pool_list)
+}
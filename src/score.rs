@@ -0,0 +1,260 @@
+/**
+ * src/score.rs
+ * Copyright (c) 2021 Carlos Carral <carloscarral13@gmail.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+use crate::grid::{Day, Schedule, TimeRange};
+use chrono::{NaiveTime, Timelike};
+use serde::{Deserialize, Serialize};
+use strum::IntoEnumIterator;
+
+const MINUTES_PER_DAY: f64 = 24.0 * 60.0;
+
+/// Assigns a cost to a schedule. Lower scores are better.
+pub trait Scorer<Id: Eq + Clone, D: Clone> {
+    fn score(&self, schedule: &Schedule<Id, D>) -> f64;
+
+    /// Optimistic estimate used to prune the search: no schedule built by
+    /// adding grids to `partial` may score lower than this.
+    /// The default never prunes anything.
+    fn bound(&self, _partial: &Schedule<Id, D>) -> f64 {
+        f64::NEG_INFINITY
+    }
+}
+
+impl<Id, D, S> Scorer<Id, D> for Box<S>
+where
+    Id: Eq + Clone,
+    D: Clone,
+    S: Scorer<Id, D> + ?Sized,
+{
+    fn score(&self, schedule: &Schedule<Id, D>) -> f64 {
+        (**self).score(schedule)
+    }
+
+    fn bound(&self, partial: &Schedule<Id, D>) -> f64 {
+        (**self).bound(partial)
+    }
+}
+
+/// Built-in scoring criteria. All of them are measured in minutes,
+/// except for `DaysOnCampus`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Criterion {
+    /// Total time spent waiting between classes on the same day.
+    IdleTime,
+    /// Number of days with at least one class.
+    DaysOnCampus,
+    /// How early the earliest class of the week starts,
+    /// counted backwards from midnight.
+    EarliestStart,
+    /// How late the latest class of the week finishes.
+    LatestFinish,
+    /// Sum of the time between the first and the last class of each day.
+    Compactness,
+    /// Total time spent getting from the location of a class to the next one
+    /// on the same day, according to the schedule's `GapRule::travel_times`.
+    TravelTime,
+}
+
+impl<Id, D> Scorer<Id, D> for Criterion
+where
+    Id: Eq + Clone,
+    D: Clone,
+{
+    fn score(&self, schedule: &Schedule<Id, D>) -> f64 {
+        let days = Day::iter().map(|day| day_ranges(schedule, day));
+
+        match self {
+            Criterion::IdleTime => days
+                .map(|ranges| {
+                    ranges
+                        .windows(2)
+                        .map(|pair| minutes(pair[1].0) - minutes(pair[0].1))
+                        .filter(|gap| *gap > 0.0)
+                        .sum::<f64>()
+                })
+                .sum(),
+            Criterion::DaysOnCampus => days.filter(|ranges| !ranges.is_empty()).count() as f64,
+            Criterion::EarliestStart => days
+                .filter_map(|ranges| {
+                    ranges
+                        .first()
+                        .map(|&(start, _)| MINUTES_PER_DAY - minutes(start))
+                })
+                .fold(0.0, f64::max),
+            Criterion::LatestFinish => days
+                .filter_map(|ranges| ranges.iter().map(|&(_, end)| minutes(end)).reduce(f64::max))
+                .fold(0.0, f64::max),
+            Criterion::Compactness => days
+                .filter_map(|ranges| {
+                    let first_start = ranges.first()?.0;
+                    let last_end = ranges.iter().map(|&(_, end)| end).max()?;
+                    Some(minutes(last_end) - minutes(first_start))
+                })
+                .sum(),
+            Criterion::TravelTime => {
+                let travel_times = match &schedule.gap().travel_times {
+                    Some(travel_times) => travel_times,
+                    None => return 0.0,
+                };
+
+                Day::iter()
+                    .map(|day| {
+                        let mut classes: Vec<_> = schedule
+                            .grids()
+                            .iter()
+                            .flat_map(|grid| {
+                                grid.time_values[day as usize]
+                                    .iter()
+                                    .map(move |range| (range, grid))
+                            })
+                            .collect();
+                        classes.sort_by_key(|&(range, _)| *range);
+
+                        classes
+                            .windows(2)
+                            .map(|pair| f64::from(travel_times.between(pair[0].1, pair[1].1)))
+                            .sum::<f64>()
+                    })
+                    .sum()
+            }
+        }
+    }
+
+    fn bound(&self, partial: &Schedule<Id, D>) -> f64 {
+        match self {
+            // Adding a class may fill the gap between two others, or sit
+            // between two far apart locations
+            Criterion::IdleTime | Criterion::TravelTime => 0.0,
+            // Adding classes can only make these grow
+            Criterion::DaysOnCampus
+            | Criterion::EarliestStart
+            | Criterion::LatestFinish
+            | Criterion::Compactness => self.score(partial),
+        }
+    }
+}
+
+/// Weighted sum of several scorers.
+/// i.e: `[(Criterion::IdleTime, 1.0), (Criterion::DaysOnCampus, 60.0)]` counts
+/// every day on campus as an extra hour of idle time.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Weighted<S> {
+    pub criteria: Vec<(S, f64)>,
+}
+
+impl<S> Weighted<S> {
+    pub fn new(criteria: Vec<(S, f64)>) -> Self {
+        Weighted { criteria }
+    }
+}
+
+impl<Id, D, S> Scorer<Id, D> for Weighted<S>
+where
+    Id: Eq + Clone,
+    D: Clone,
+    S: Scorer<Id, D>,
+{
+    fn score(&self, schedule: &Schedule<Id, D>) -> f64 {
+        self.criteria
+            .iter()
+            .map(|(scorer, weight)| weight * scorer.score(schedule))
+            .sum()
+    }
+
+    fn bound(&self, partial: &Schedule<Id, D>) -> f64 {
+        self.criteria
+            .iter()
+            .map(|(scorer, weight)| {
+                if *weight >= 0.0 {
+                    weight * scorer.bound(partial)
+                } else {
+                    // Nothing is known about how large the score may get
+                    f64::NEG_INFINITY
+                }
+            })
+            .sum()
+    }
+}
+
+/// Sorts schedules from best to worst. Schedules with the same score keep
+/// their relative order.
+pub fn rank<Id, D, S>(schedules: Vec<Schedule<Id, D>>, scorer: &S) -> Vec<Schedule<Id, D>>
+where
+    Id: Eq + Clone,
+    D: Clone,
+    S: Scorer<Id, D> + ?Sized,
+{
+    let mut scored: Vec<_> = schedules
+        .into_iter()
+        .map(|schedule| (scorer.score(&schedule), schedule))
+        .collect();
+
+    scored.sort_by(|(a, _), (b, _)| a.total_cmp(b));
+
+    scored.into_iter().map(|(_, schedule)| schedule).collect()
+}
+
+/// All the time ranges of a schedule on a given day, sorted by start time.
+fn day_ranges<Id, D>(schedule: &Schedule<Id, D>, day: Day) -> Vec<TimeRange>
+where
+    Id: Eq + Clone,
+    D: Clone,
+{
+    let mut ranges: Vec<TimeRange> = schedule
+        .grids()
+        .iter()
+        .flat_map(|grid| grid.time_values[day as usize].iter().copied())
+        .collect();
+    ranges.sort();
+    ranges
+}
+
+fn minutes(time: NaiveTime) -> f64 {
+    f64::from(time.num_seconds_from_midnight()) / 60.0
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::utils::*;
+
+    #[test]
+    fn test_criteria() {
+        let mut schedule = Schedule::new();
+        schedule.try_merge(&instance_grid_1().unwrap()).unwrap();
+        schedule.try_merge(&instance_grid_2().unwrap()).unwrap();
+
+        // Monday 08:00 - 10:00 and Friday 07:00 - 09:00, back to back
+        assert_eq!(Criterion::IdleTime.score(&schedule), 0.0);
+        assert_eq!(Criterion::DaysOnCampus.score(&schedule), 2.0);
+        assert_eq!(Criterion::EarliestStart.score(&schedule), 17.0 * 60.0);
+        assert_eq!(Criterion::LatestFinish.score(&schedule), 10.0 * 60.0);
+        assert_eq!(Criterion::Compactness.score(&schedule), 2.0 * 120.0);
+
+        let empty = Schedule::<u32, u32>::new();
+        assert_eq!(
+            Weighted::new(vec![
+                (Criterion::EarliestStart, 1.0),
+                (Criterion::LatestFinish, 1.0)
+            ])
+            .score(&empty),
+            0.0
+        );
+    }
+}
@@ -0,0 +1,168 @@
+/**
+ * src/travel.rs
+ * Copyright (c) 2021 Carlos Carral <carloscarral13@gmail.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+use crate::grid::Grid;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+
+/// Minutes it takes to get from one location to another.
+///
+/// Lookups are symmetric, so each pair only needs to be listed once.
+/// i.e: `{ "north": { "south": 30, "downtown": 45 } }`
+///
+/// The engine requires the travel time between every two classes on the same
+/// day, not only between consecutive ones. Both are the same as long as going
+/// through a third location is never faster, so `GapRule::with_travel_times`
+/// first cuts each listed time down to the fastest route. Locations and pairs
+/// that aren't listed take no time and are never part of a route.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct TravelTimes {
+    minutes: HashMap<String, HashMap<String, u32>>,
+}
+
+impl TravelTimes {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, from: impl Into<String>, to: impl Into<String>, minutes: u32) {
+        self.minutes
+            .entry(from.into())
+            .or_default()
+            .insert(to.into(), minutes);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.minutes.values().all(HashMap::is_empty)
+    }
+
+    /// Minutes between `from` and `to`. Staying in the same place and pairs
+    /// that aren't listed take no time.
+    pub fn minutes(&self, from: &str, to: &str) -> u32 {
+        if from == to {
+            return 0;
+        }
+
+        let lookup = |a: &str, b: &str| self.minutes.get(a).and_then(|row| row.get(b)).copied();
+        lookup(from, to).or_else(|| lookup(to, from)).unwrap_or(0)
+    }
+
+    /// The same travel times, each cut down to the fastest route through
+    /// other listed locations. Pairs that aren't listed stay that way.
+    pub fn fastest_routes(&self) -> Self {
+        let locations: Vec<&str> = self
+            .minutes
+            .iter()
+            .flat_map(|(from, row)| {
+                std::iter::once(from.as_str()).chain(row.keys().map(String::as_str))
+            })
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect();
+        let index = |location: &str| locations.binary_search(&location).unwrap();
+
+        let n = locations.len();
+        let mut fastest: Vec<Vec<Option<u32>>> = vec![vec![None; n]; n];
+        for (from, row) in self.minutes.iter() {
+            for (to, &minutes) in row.iter() {
+                let (i, j) = (index(from), index(to));
+                if i != j {
+                    let shortest = fastest[i][j].map_or(minutes, |known| known.min(minutes));
+                    fastest[i][j] = Some(shortest);
+                    fastest[j][i] = Some(shortest);
+                }
+            }
+        }
+
+        let mut routes = fastest.clone();
+        for via in 0..n {
+            for from in 0..n {
+                for to in 0..n {
+                    if let (Some(first), Some(second)) = (routes[from][via], routes[via][to]) {
+                        let through = first.saturating_add(second);
+                        if from != to && routes[from][to].is_none_or(|direct| through < direct) {
+                            routes[from][to] = Some(through);
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut shortened = TravelTimes::new();
+        for (from, row) in self.minutes.iter() {
+            for to in row.keys() {
+                let (i, j) = (index(from), index(to));
+                let minutes = if i == j { 0 } else { routes[i][j].unwrap() };
+                shortened.insert(from.clone(), to.clone(), minutes);
+            }
+        }
+        shortened
+    }
+
+    /// Minutes between the locations of `a` and `b`, or 0 if any of them
+    /// lacks one.
+    pub fn between<Id: Eq, D>(&self, a: &Grid<Id, D>, b: &Grid<Id, D>) -> u32 {
+        match (&a.location, &b.location) {
+            (Some(from), Some(to)) => self.minutes(from, to),
+            _ => 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_travel_times() {
+        let travel_times: TravelTimes =
+            serde_json::from_str(r#"{ "north": { "south": 30, "downtown": 45 } }"#).unwrap();
+
+        assert_eq!(travel_times.minutes("north", "south"), 30);
+        assert_eq!(travel_times.minutes("south", "north"), 30);
+        assert_eq!(travel_times.minutes("downtown", "north"), 45);
+        assert_eq!(travel_times.minutes("south", "south"), 0);
+        assert_eq!(travel_times.minutes("south", "downtown"), 0);
+        assert!(!travel_times.is_empty());
+        assert!(TravelTimes::new().is_empty());
+
+        // Going through south is faster than going straight downtown
+        let mut routes = travel_times.clone();
+        routes.insert("south", "downtown", 10);
+        let fastest = routes.fastest_routes();
+        assert_eq!(fastest.minutes("north", "downtown"), 40);
+        assert_eq!(fastest.minutes("north", "south"), 30);
+        assert_eq!(fastest.minutes("downtown", "south"), 10);
+
+        // Without a listed route, downtown and south stay no time apart
+        let fastest = travel_times.fastest_routes();
+        assert_eq!(fastest, travel_times);
+        assert_eq!(fastest.minutes("south", "downtown"), 0);
+
+        let grid = |location: Option<&str>| {
+            let mut grid = Grid::new(1, Default::default(), ()).unwrap();
+            grid.location = location.map(String::from);
+            grid
+        };
+        assert_eq!(
+            travel_times.between(&grid(Some("north")), &grid(Some("south"))),
+            30
+        );
+        assert_eq!(travel_times.between(&grid(Some("north")), &grid(None)), 0);
+    }
+}
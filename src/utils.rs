@@ -1,117 +1,131 @@
+use crate::error::ScheduleError;
 use crate::grid::Grid;
-use anyhow::Result;
 use chrono::NaiveTime;
 
+/// Parses a time formatted as "HH:MM".
+pub fn time(value: &str) -> NaiveTime {
+    NaiveTime::parse_from_str(value, "%H:%M").unwrap()
+}
+
+/// Time values for `Grid::from_vec` with classes only on Monday, from `begin`
+/// to `end`. Several ranges are separated by ';'.
+pub fn monday(begin: &str, end: &str) -> [String; 14] {
+    let mut time_values: [String; 14] = Default::default();
+    time_values[0] = begin.to_string();
+    time_values[1] = end.to_string();
+    time_values
+}
+
 #[allow(dead_code)]
 pub fn set_panic_hook() {
     #[cfg(feature = "console_error_panic_hook")]
     console_error_panic_hook::set_once();
 }
 
-pub fn instance_grid_1() -> Result<Grid<u32, u32>> {
+pub fn instance_grid_1() -> Result<Grid<u32, u32>, ScheduleError<u32>> {
     Grid::new(
         1,
         [
-            Some((
+            vec![(
                 NaiveTime::parse_from_str("08:00", "%H:%M").unwrap(),
                 NaiveTime::parse_from_str("09:00", "%H:%M").unwrap(),
-            )),
-            None,
-            None,
-            None,
-            Some((
+            )],
+            vec![],
+            vec![],
+            vec![],
+            vec![(
                 NaiveTime::parse_from_str("08:00", "%H:%M").unwrap(),
                 NaiveTime::parse_from_str("09:00", "%H:%M").unwrap(),
-            )),
-            None,
-            None,
+            )],
+            vec![],
+            vec![],
         ],
         0,
     )
 }
 
-pub fn instance_grid_2() -> Result<Grid<u32, u32>> {
+pub fn instance_grid_2() -> Result<Grid<u32, u32>, ScheduleError<u32>> {
     Grid::new(
         2,
         [
-            Some((
+            vec![(
                 NaiveTime::parse_from_str("09:00", "%H:%M").unwrap(),
                 NaiveTime::parse_from_str("10:00", "%H:%M").unwrap(),
-            )),
-            None,
-            None,
-            None,
-            Some((
+            )],
+            vec![],
+            vec![],
+            vec![],
+            vec![(
                 NaiveTime::parse_from_str("07:00", "%H:%M").unwrap(),
                 NaiveTime::parse_from_str("08:00", "%H:%M").unwrap(),
-            )),
-            None,
-            None,
+            )],
+            vec![],
+            vec![],
         ],
         0,
     )
 }
 
-pub fn instance_grid_3() -> Result<Grid<u32, u32>> {
+pub fn instance_grid_3() -> Result<Grid<u32, u32>, ScheduleError<u32>> {
     Grid::new(
         3,
         [
-            Some((
+            vec![(
                 NaiveTime::parse_from_str("08:30", "%H:%M").unwrap(),
                 NaiveTime::parse_from_str("11:00", "%H:%M").unwrap(),
-            )),
-            None,
-            Some((
+            )],
+            vec![],
+            vec![(
                 NaiveTime::parse_from_str("12:00", "%H:%M").unwrap(),
                 NaiveTime::parse_from_str("13:45", "%H:%M").unwrap(),
-            )),
-            None,
-            None,
-            None,
-            None,
+            )],
+            vec![],
+            vec![],
+            vec![],
+            vec![],
         ],
         0,
     )
 }
 
-pub fn instance_grid_4() -> Result<Grid<u32, u32>> {
+pub fn instance_grid_4() -> Result<Grid<u32, u32>, ScheduleError<u32>> {
     Grid::new(
         3,
         [
-            None,
-            Some((
+            vec![],
+            vec![(
                 NaiveTime::parse_from_str("16:30", "%H:%M").unwrap(),
                 NaiveTime::parse_from_str("18:30", "%H:%M").unwrap(),
-            )),
-            None,
-            Some((
+            )],
+            vec![],
+            vec![(
                 NaiveTime::parse_from_str("16:30", "%H:%M").unwrap(),
                 NaiveTime::parse_from_str("18:30", "%H:%M").unwrap(),
-            )),
-            None,
-            None,
-            None,
+            )],
+            vec![],
+            vec![],
+            vec![],
         ],
         0,
     )
 }
-pub fn instance_grid_5() -> Result<Grid<u32, u32>> {
+pub fn instance_grid_5() -> Result<Grid<u32, u32>, ScheduleError<u32>> {
     Grid::new(
         3,
         [
-            None,
-            Some((
+            vec![],
+            vec![(
                 NaiveTime::parse_from_str("18:00", "%H:%M").unwrap(),
                 NaiveTime::parse_from_str("21:00", "%H:%M").unwrap(),
-            )),
-            None,
-            None,
-            Some((
+            )],
+            vec![],
+            vec![],
+            vec![(
                 NaiveTime::parse_from_str("07:00", "%H:%M").unwrap(),
                 NaiveTime::parse_from_str("09:00", "%H:%M").unwrap(),
-            )),
-            None,
-            None,
+            )],
+            vec![],
+            vec![],
         ],
         0,
     )
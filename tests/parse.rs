@@ -1,6 +1,93 @@
-// use schedule_engine::parse::*;
-#![cfg(test)]
+use schedule_engine::error::ScheduleError;
+use schedule_engine::parse::*;
+
+const HEADER: &str = "subject,group,mon_b,mon_e,tue_b,tue_e,wed_b,wed_e,thu_b,thu_e,fri_b,fri_e,sat_b,sat_e,sun_b,sun_e\n";
+
+fn layout() -> CsvLayout {
+    CsvLayout {
+        time_columns_start: 2,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn groups_rows_into_pools() {
+    let input = format!(
+        "{}{}{}{}",
+        HEADER,
+        "physics,1,08:00,10:00,,,08:00,10:00,,,,,,,,\n",
+        "spanish,1,,,09:00,11:00,,,,,,,,,,\n",
+        "physics,2,16:00;18:00,17:00;19:00,,,,,,,,,,,,\n",
+    );
+
+    let pool_list = read_pools(input.as_bytes(), &layout()).unwrap();
+
+    assert_eq!(pool_list.len(), 2);
+    assert_eq!(pool_list[0].pool_id, "physics");
+    assert_eq!(pool_list[0].grids().len(), 2);
+    assert_eq!(pool_list[1].pool_id, "spanish");
+    assert_eq!(pool_list[1].grids().len(), 1);
+
+    let lab = &pool_list[0].grids()[1];
+    assert_eq!(lab.time_values[0].len(), 2);
+    assert_eq!(lab.data()["group"], "2");
+    assert!(!lab.data().contains_key("subject"));
+}
+
+#[test]
+fn custom_layout() {
+    let input = "group,mon_b,mon_e,tue_b,tue_e,wed_b,wed_e,thu_b,thu_e,fri_b,fri_e,sat_b,sat_e,sun_b,sun_e,subject\n\
+                 3,08:00 AM,10:00 AM,,,,,,,,,,,,,chemistry\n";
+    let layout = CsvLayout {
+        pool_id_column: 15,
+        time_columns_start: 1,
+        time_format: "%I:%M %p".to_string(),
+    };
+
+    let pool_list = read_pools(input.as_bytes(), &layout).unwrap();
+
+    assert_eq!(pool_list[0].pool_id, "chemistry");
+    assert_eq!(pool_list[0].grids()[0].data()["group"], "3");
+}
+
+#[test]
+fn reports_malformed_slice() {
+    let input = format!(
+        "{}{}{}",
+        HEADER, "physics,1,08:00,10:00,,,,,,,,,,,,\n", "physics,2,,,09:00,,,,,,,,,,,\n",
+    );
+
+    match read_pools(input.as_bytes(), &layout()) {
+        Err(ParseError::InvalidGrid {
+            row: 3,
+            column: 5,
+            source: ScheduleError::MalformedSlice { index: 3 },
+        }) => {}
+        other => panic!("unexpected result: {:?}", other.map(|_| ())),
+    }
+}
+
 #[test]
-fn pass() {
-    assert!(true);
+fn reports_bad_times() {
+    let input = format!("{}{}", HEADER, "physics,1,,,,,8h,10:00,,,,,,,,\n");
+
+    match read_pools(input.as_bytes(), &layout()) {
+        Err(ParseError::InvalidGrid {
+            row: 2,
+            column: 6,
+            source: ScheduleError::InvalidTime { value, .. },
+        }) => assert_eq!(value, "8h"),
+        other => panic!("unexpected result: {:?}", other.map(|_| ())),
+    }
+
+    let input = format!("{}{}", HEADER, "physics,1,,,,,,,,,11:00,10:00,,,,\n");
+
+    match read_pools(input.as_bytes(), &layout()) {
+        Err(ParseError::InvalidGrid {
+            row: 2,
+            column: 10,
+            source: ScheduleError::InvalidTimeRange { .. },
+        }) => {}
+        other => panic!("unexpected result: {:?}", other.map(|_| ())),
+    }
 }
@@ -1,9 +1,9 @@
 #![cfg(target_arch = "wasm32")]
 
 extern crate wasm_bindgen_test;
-use anyhow::Result;
 use chrono::NaiveTime;
-use schedule_engine::{engine::*, grid::*, log, utils::*};
+use schedule_engine::{engine::*, engine_main_js, grid::*, log, utils::*};
+use serde::Serialize;
 use std::collections::hash_map::HashMap;
 use wasm_bindgen::prelude::JsValue;
 use wasm_bindgen_test::*;
@@ -15,16 +15,16 @@ fn raises_invalid_time() {
     let grid = Grid::new(
         0,
         [
-            Some((
+            vec![(
                 NaiveTime::parse_from_str("09:00", "%H:%M").unwrap(),
                 NaiveTime::parse_from_str("08:00", "%H:%M").unwrap(),
-            )),
-            None,
-            None,
-            None,
-            None,
-            None,
-            None,
+            )],
+            vec![],
+            vec![],
+            vec![],
+            vec![],
+            vec![],
+            vec![],
         ],
         0,
     );
@@ -107,7 +107,7 @@ fn test_from_vec() {
 
 #[wasm_bindgen_test]
 fn test_deserialize() {
-    let time_values = [None; 7];
+    let time_values: [Vec<TimeRange>; 7] = Default::default();
     let mut data = std::collections::hash_map::HashMap::<String, String>::new();
 
     data.insert("professor".to_string(), "Stephen Hawking".to_string());
@@ -117,3 +117,43 @@ fn test_deserialize() {
     log!("{:?}", grid);
     log!("{:?}", serialized);
 }
+
+#[wasm_bindgen_test]
+fn test_engine_main_js() {
+    let params = serde_json::json!({
+        "seeds": [],
+        "bound": 1,
+        "pool_list": [{
+            "pool_id": "physics",
+            "grid_list": [{
+                "pool_id": "physics",
+                "time_values": [[["08:00:00", "09:00:00"]], [], [], [], [], [], []],
+                "data": { "teacher": "Stephen Hawking" }
+            }]
+        }]
+    });
+    let params = params
+        .serialize(&serde_wasm_bindgen::Serializer::json_compatible())
+        .unwrap();
+
+    let schedules = engine_main_js(params).unwrap();
+    let schedules: serde_json::Value = serde_wasm_bindgen::from_value(schedules).unwrap();
+
+    assert_eq!(schedules.as_array().unwrap().len(), 1);
+    assert_eq!(
+        schedules[0]["grids"][0]["data"]["teacher"],
+        "Stephen Hawking"
+    );
+}
+
+#[wasm_bindgen_test]
+fn test_engine_main_js_throws() {
+    let params = serde_json::json!({ "seeds": [], "bound": 3, "pool_list": [] });
+    let params = params
+        .serialize(&serde_wasm_bindgen::Serializer::json_compatible())
+        .unwrap();
+
+    let err = engine_main_js(params).unwrap_err();
+
+    assert!(err.is_instance_of::<js_sys::Error>());
+}